@@ -0,0 +1,181 @@
+//! Generates puzzle packs natively, one JSON object per line.
+//!
+//! ```text
+//! futoshiki-batch --difficulty Hard --count 100 [--start 0] [--size 9]
+//!                 [--minimize true] [--symmetry Rotational]
+//!                 [--symmetric-inequalities true] [--max-cage-size 4]
+//!                 [--dots true] [--all-dots true] [--thermometers 6]
+//!                 [--regions Diagonals,Windoku] [--jigsaw true] [--threads 8]
+//!                 [--output hard.jsonl]
+//! ```
+//!
+//! Puzzle `k` uses seed `start + k`, so a pack can be rebuilt or extended
+//! exactly. Lines are written as puzzles finish, not in seed order; seeds
+//! that fail to generate are reported on stderr and skipped.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use futoshiki_core::futoshiki::Puzzle;
+use futoshiki_core::generator::{self, Budget, Difficulty, GenerateOptions};
+use futoshiki_core::rating::{self, Rating};
+use futoshiki_core::solver::SolverStats;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+const USAGE: &str = "usage: futoshiki-batch --difficulty <name> --count <n> [--start <seed>] [--size <n>] [--minimize <true|false>] [--symmetry <name>] [--symmetric-inequalities <true|false>] [--max-cage-size <n>] [--dots <true|false>] [--all-dots <true|false>] [--thermometers <n>] [--regions <name,...>] [--jigsaw <true|false>] [--threads <n>] [--output <file>]";
+
+struct Options {
+    difficulty: String,
+    count: u64,
+    start: u64,
+    generate: GenerateOptions,
+    threads: usize,
+    output: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    seed: u64,
+    difficulty: &'a str,
+    hints: usize,
+    constraints: usize,
+    rating: Rating,
+    /// Solver work on the final uniqueness check.
+    stats: SolverStats,
+    generation_ms: f64,
+    /// Every attempt missed the clue range or rating band.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    fallback: bool,
+    puzzle: Puzzle,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        difficulty: String::new(),
+        count: 0,
+        start: 0,
+        generate: GenerateOptions::default(),
+        threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        output: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        if flag == "--help" || flag == "-h" {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        let value = args.next().ok_or_else(|| format!("missing value for {}", flag))?;
+        let number = |v: &str| v.parse::<u64>().map_err(|_| format!("invalid number for {}: {}", flag, v));
+        let boolean = |v: &str| v.parse::<bool>().map_err(|_| format!("invalid value for {}: {}", flag, v));
+        match flag.as_str() {
+            "--difficulty" => options.difficulty = value,
+            "--count" => options.count = number(&value)?,
+            "--start" => options.start = number(&value)?,
+            "--size" => options.generate.size = Some(number(&value)? as usize),
+            "--minimize" => options.generate.minimize = boolean(&value)?,
+            "--symmetry" => options.generate.symmetry = value.parse()?,
+            "--symmetric-inequalities" => options.generate.symmetric_inequalities = boolean(&value)?,
+            "--max-cage-size" => options.generate.max_cage_size = Some(number(&value)? as usize),
+            "--dots" => options.generate.dots = boolean(&value)?,
+            "--all-dots" => options.generate.all_dots = boolean(&value)?,
+            "--thermometers" => options.generate.thermometers = number(&value)? as usize,
+            "--regions" => options.generate.regions = value.split(',').map(str::parse).collect::<Result<_, _>>()?,
+            "--jigsaw" => options.generate.jigsaw = boolean(&value)?,
+            "--threads" => options.threads = number(&value)?.max(1) as usize,
+            "--output" => options.output = Some(value),
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+
+    if options.difficulty.is_empty() {
+        return Err("--difficulty is required".to_string());
+    }
+    options.difficulty.parse::<Difficulty>().map_err(|err| err.to_string())?;
+    Ok(options)
+}
+
+fn main() {
+    let options = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+
+    let mut out: Box<dyn Write> = match &options.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("cannot create {}: {}", path, err);
+                process::exit(1);
+            }
+        },
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    let next = AtomicU64::new(0);
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..options.threads {
+            let tx = tx.clone();
+            let (next, options) = (&next, &options);
+            scope.spawn(move || loop {
+                let k = next.fetch_add(1, Ordering::Relaxed);
+                if k >= options.count {
+                    break;
+                }
+                let seed = options.start + k;
+                let started = Instant::now();
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                let result = generator::generate_with_budget(&options.difficulty, &options.generate, &mut rng, Budget::default(), |_| true);
+                let generation_ms = started.elapsed().as_secs_f64() * 1000.0;
+                let result = result.map(|candidate| {
+                    let rating = rating::rate(&candidate.puzzle);
+                    (candidate, rating)
+                });
+                if tx.send((seed, result, generation_ms)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        for (seed, result, generation_ms) in rx {
+            let (candidate, rating) = match result {
+                Ok(generated) => generated,
+                Err(err) => {
+                    eprintln!("seed {}: {}", seed, err);
+                    continue;
+                }
+            };
+            let puzzle = candidate.puzzle;
+            let record = Record {
+                seed,
+                difficulty: &options.difficulty,
+                hints: puzzle.grid.iter().filter(|&&v| v > 0).count(),
+                constraints: puzzle.constraints.len(),
+                rating,
+                stats: candidate.stats,
+                generation_ms,
+                fallback: candidate.fallback,
+                puzzle,
+            };
+            let line = serde_json::to_string(&record).expect("record serializes");
+            if let Err(err) = writeln!(out, "{}", line) {
+                eprintln!("write failed: {}", err);
+                process::exit(1);
+            }
+        }
+    });
+
+    if let Err(err) = out.flush() {
+        eprintln!("write failed: {}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,171 @@
+//! Pencil-mark candidates for a board in progress, with the reason each
+//! removed value is gone.
+//!
+//! Entered and given values clear themselves from their peers, then the
+//! inequalities and thermometer segments trim bounds until nothing changes:
+//! the smaller cell stays below the largest candidate of the larger one and
+//! vice versa, so bounds run along whole chains.
+
+use serde::Serialize;
+use crate::futoshiki::{Inequality, Puzzle};
+use crate::geometry::House;
+use crate::solver::{self, State};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Reason {
+    /// `cell` in `house` already holds the value.
+    Peer {
+        cell: usize,
+        house: House,
+    },
+    /// The value breaks the bounds of `value[a] < value[b]`.
+    /// `index` points into `Puzzle::constraints`.
+    Inequality {
+        index: usize,
+        a: usize,
+        b: usize,
+    },
+    /// The value breaks the bounds of the thermometer segment from `a` up
+    /// to `b`. `index` points into `Puzzle::thermos`.
+    Thermo {
+        index: usize,
+        a: usize,
+        b: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct Elimination {
+    pub cell: usize,
+    pub value: u8,
+    pub reason: Reason,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Candidates {
+    /// One mask per cell, bit v-1 = candidate v. A filled cell holds just
+    /// its value; an empty cell left with 0 has no possible value.
+    pub candidates: Vec<u16>,
+    /// Every value removed from an empty cell, with the first reason found.
+    pub eliminations: Vec<Elimination>,
+}
+
+/// Candidates for the givens of `puzzle` overlaid with `entries` (one per
+/// cell, 0 or -1 = empty). Entries on given cells are ignored.
+pub fn candidates(puzzle: &Puzzle, entries: &[i8]) -> Candidates {
+    let geometry = &puzzle.geometry;
+    let board = puzzle.board(entries);
+
+    let mut state = State::new(geometry);
+    let mut eliminations = Vec::new();
+    let mut restrict = |state: &mut State, cell: usize, mask: u16, reason: Reason| {
+        let removed = state.domains[cell] & !mask;
+        for value in (1..=geometry.size as u8).filter(|&v| removed & 1 << (v - 1) != 0) {
+            eliminations.push(Elimination { cell, value, reason });
+        }
+        state.domains[cell] &= mask;
+        removed != 0
+    };
+
+    for (idx, &value) in board.iter().enumerate() {
+        if value != 0 {
+            state.domains[idx] = 1 << (value - 1);
+        }
+    }
+    for idx in (0..board.len()).filter(|&i| board[i] == 0) {
+        for house in geometry.houses_of(idx) {
+            for cell in house.cells(geometry).into_iter().filter(|&j| board[j] != 0) {
+                restrict(&mut state, idx, !(1 << (board[cell] - 1)), Reason::Peer { cell, house });
+            }
+        }
+    }
+
+    let mut bounds: Vec<(Inequality, Reason)> = puzzle.constraints.iter().enumerate()
+        .map(|(index, &ineq)| (ineq, Reason::Inequality { index, a: ineq.a, b: ineq.b }))
+        .collect();
+    for (index, thermo) in puzzle.thermos.iter().enumerate() {
+        bounds.extend(thermo.cells.windows(2).map(|pair| {
+            (Inequality { a: pair[0], b: pair[1] }, Reason::Thermo { index, a: pair[0], b: pair[1] })
+        }));
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &(ineq, reason) in &bounds {
+            let (domain_a, domain_b) = (state.domains[ineq.a], state.domains[ineq.b]);
+            if board[ineq.a] == 0 && domain_b != 0 {
+                let below = solver::filter_lt(geometry.all_values(), solver::get_max(domain_b));
+                changed |= restrict(&mut state, ineq.a, below, reason);
+            }
+            if board[ineq.b] == 0 && domain_a != 0 {
+                let above = solver::filter_gt(geometry.all_values(), solver::get_min(domain_a));
+                changed |= restrict(&mut state, ineq.b, above, reason);
+            }
+        }
+    }
+
+    Candidates { candidates: state.domains, eliminations }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::futoshiki::Thermo;
+    use crate::geometry::Geometry;
+
+    #[test]
+    fn test_peers_and_inequality_chain() {
+        // Latin 4x4 with 1 < 2 < 3 along the top row and a 4 entered in
+        // cell 3: the chain climbs to 1, 2, 3 and explains each step.
+        let puzzle = Puzzle {
+            geometry: Geometry::latin(4),
+            grid: vec![-1; 16],
+            constraints: vec![Inequality { a: 0, b: 1 }, Inequality { a: 1, b: 2 }],
+            ..Puzzle::default()
+        };
+        let mut entries = vec![0i8; 16];
+        entries[3] = 4;
+        let result = candidates(&puzzle, &entries);
+        assert_eq!(&result.candidates[..4], &[0b0001, 0b0010, 0b0100, 0b1000]);
+        assert_eq!(result.candidates[7], 0b0111);
+
+        let why = |cell: usize, value: u8| result.eliminations.iter().find(|e| (e.cell, e.value) == (cell, value)).unwrap().reason;
+        assert_eq!(why(7, 4), Reason::Peer { cell: 3, house: House::Column(3) });
+        assert_eq!(why(2, 4), Reason::Peer { cell: 3, house: House::Row(0) });
+        assert_eq!(why(0, 3), Reason::Inequality { index: 0, a: 0, b: 1 });
+        assert_eq!(why(2, 1), Reason::Inequality { index: 1, a: 1, b: 2 });
+        assert!(result.eliminations.iter().all(|e| result.candidates[e.cell] & 1 << (e.value - 1) == 0));
+        assert_eq!(result.eliminations.iter().filter(|e| e.cell < 3).count(), 9);
+    }
+
+    #[test]
+    fn test_contradiction_empties_the_cell() {
+        let puzzle = Puzzle {
+            geometry: Geometry::latin(3),
+            grid: vec![3, -1, -1, -1, -1, -1, -1, -1, -1],
+            constraints: vec![Inequality { a: 0, b: 1 }],
+            ..Puzzle::default()
+        };
+        let result = candidates(&puzzle, &[]);
+        assert_eq!(result.candidates[0], 0b100);
+        assert_eq!(result.candidates[1], 0);
+    }
+
+    #[test]
+    fn test_thermometer_bounds() {
+        // A thermometer up the first column of a Latin 4x4 fixes 1, 2, 3, 4.
+        let puzzle = Puzzle {
+            geometry: Geometry::latin(4),
+            grid: vec![-1; 16],
+            thermos: vec![Thermo { cells: vec![12, 8, 4, 0] }],
+            ..Puzzle::default()
+        };
+        let result = candidates(&puzzle, &[]);
+        assert_eq!([12, 8, 4, 0].map(|i| result.candidates[i]), [0b0001, 0b0010, 0b0100, 0b1000]);
+        let why = |cell: usize, value: u8| result.eliminations.iter().find(|e| (e.cell, e.value) == (cell, value)).unwrap().reason;
+        assert_eq!(why(12, 4), Reason::Thermo { index: 0, a: 12, b: 8 });
+        assert_eq!(why(0, 3), Reason::Thermo { index: 0, a: 4, b: 0 });
+    }
+}
@@ -1,10 +1,13 @@
 use serde::{Serialize, Deserialize};
+use rand::prelude::*;
+use std::fmt;
 use crate::sudoku::Grid;
+use crate::geometry::Geometry;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Inequality {
-    pub a: usize, // index 0-80
-    pub b: usize, // index 0-80
+    pub a: usize, // cell index, row-major
+    pub b: usize, // cell index, row-major
     // dir is redundant if we always store such that constraint is a < b, 
     // but useful for UI or if we want to scramble order. 
     // Let's standard: "value[a] < value[b]"
@@ -12,59 +15,472 @@ pub struct Inequality {
 }
 
 impl Inequality {
-    pub fn is_intra_block(&self) -> bool {
-        let (r_a, c_a) = (self.a / 9, self.a % 9);
-        let (r_b, c_b) = (self.b / 9, self.b % 9);
-        
-        let blk_a = (r_a / 3) * 3 + (c_a / 3);
-        let blk_b = (r_b / 3) * 3 + (c_b / 3);
-        
-        blk_a == blk_b
+    /// Latin boards have no boxes, so nothing is intra-block there.
+    pub fn is_intra_block(&self, geometry: &Geometry) -> bool {
+        let blk_a = geometry.box_of(self.a);
+        let blk_b = geometry.box_of(self.b);
+
+        blk_a.is_some() && blk_a == blk_b
+    }
+}
+
+/// Killer cage: the values in `cells` add up to `sum` and do not repeat.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Cage {
+    pub cells: Vec<usize>,
+    pub sum: u32,
+}
+
+/// Kropki dot colours.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DotKind {
+    /// The two values differ by 1.
+    White,
+    /// One value is double the other.
+    Black,
+}
+
+impl DotKind {
+    /// Whether values `x` and `y` satisfy the dot.
+    pub fn holds(&self, x: u8, y: u8) -> bool {
+        match self {
+            DotKind::White => x.abs_diff(y) == 1,
+            DotKind::Black => x == 2 * y || y == 2 * x,
+        }
+    }
+
+    /// The dot that `x` and `y` qualify for, if any. 1 and 2 qualify for
+    /// both and get a black dot.
+    pub fn between(x: u8, y: u8) -> Option<DotKind> {
+        [DotKind::Black, DotKind::White].into_iter().find(|kind| kind.holds(x, y))
+    }
+}
+
+/// Kropki dot between orthogonal neighbours `a` and `b`, in either order.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Dot {
+    pub a: usize,
+    pub b: usize,
+    pub kind: DotKind,
+}
+
+/// Thermometer: values strictly increase from the bulb `cells[0]` to the
+/// tip, each cell orthogonally or diagonally adjacent to the previous one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Thermo {
+    pub cells: Vec<usize>,
+}
+
+/// One rule besides the all-different houses, as the solver sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    Inequality(Inequality),
+    Cage(Cage),
+    Dot(Dot),
+    /// Neither dot holds between `a` and `b`: the negative constraint of a
+    /// puzzle that marks every qualifying pair.
+    NoDot { a: usize, b: usize },
+    Thermo(Thermo),
+}
+
+impl From<Inequality> for Constraint {
+    fn from(ineq: Inequality) -> Self {
+        Constraint::Inequality(ineq)
+    }
+}
+
+impl From<Cage> for Constraint {
+    fn from(cage: Cage) -> Self {
+        Constraint::Cage(cage)
     }
 }
 
-#[derive(Serialize, Deserialize)]
+impl From<Dot> for Constraint {
+    fn from(dot: Dot) -> Self {
+        Constraint::Dot(dot)
+    }
+}
+
+impl From<Thermo> for Constraint {
+    fn from(thermo: Thermo) -> Self {
+        Constraint::Thermo(thermo)
+    }
+}
+
+impl Constraint {
+    /// Cells the rule restricts.
+    pub fn cells(&self) -> Vec<usize> {
+        match self {
+            Constraint::Inequality(ineq) => vec![ineq.a, ineq.b],
+            Constraint::Cage(cage) => cage.cells.clone(),
+            Constraint::Dot(dot) => vec![dot.a, dot.b],
+            Constraint::NoDot { a, b } => vec![*a, *b],
+            Constraint::Thermo(thermo) => thermo.cells.clone(),
+        }
+    }
+}
+
+/// Why a puzzle from outside the crate cannot be worked on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PuzzleError {
+    /// See `Geometry::is_valid`.
+    InvalidGeometry,
+    /// `grid` does not hold one value per cell.
+    GridLength { expected: usize, found: usize },
+    /// A clue or entry outside -1..=size.
+    InvalidValue { cell: usize, value: i8 },
+    /// A rule names a cell off the board, or the same cell twice.
+    InvalidCell(usize),
+    /// A thermometer with fewer than two cells, or with a cell that does
+    /// not touch the one before it. Holds the index into `Puzzle::thermos`.
+    InvalidThermo(usize),
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PuzzleError::InvalidGeometry => write!(f, "invalid board size, boxes or regions"),
+            PuzzleError::GridLength { expected, found } => write!(f, "grid has {} cells, expected {}", found, expected),
+            PuzzleError::InvalidValue { cell, value } => write!(f, "value {} in cell {} is out of range", value, cell),
+            PuzzleError::InvalidCell(cell) => write!(f, "rule refers to invalid cell {}", cell),
+            PuzzleError::InvalidThermo(index) => write!(f, "thermometer {} is not a path of touching cells", index),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Puzzle {
+    /// Board shape; puzzles serialized before it existed are classic 9x9.
+    #[serde(default)]
+    pub geometry: Geometry,
     pub grid: Vec<i8>, // Initial numbers. -1 for empty.
     pub constraints: Vec<Inequality>,
+    /// Killer cages, empty for plain Futoshiki.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cages: Vec<Cage>,
+    /// Kropki dots, empty for plain Futoshiki.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dots: Vec<Dot>,
+    /// Every qualifying pair carries a dot, so a missing dot rules out both
+    /// relations.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub all_dots: bool,
+    /// Thermometers, empty for plain Futoshiki.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub thermos: Vec<Thermo>,
+}
+
+impl Puzzle {
+    /// The clues as a solver grid (0 = empty).
+    pub fn givens(&self) -> Grid {
+        let mut grid = vec![0u8; self.geometry.cells()];
+        for (cell, &val) in grid.iter_mut().zip(&self.grid) {
+            if val > 0 {
+                *cell = val as u8;
+            }
+        }
+        grid
+    }
+
+    /// The givens overlaid with `entries` (one per cell, 0 or -1 = empty)
+    /// as a solver grid. Entries on given cells are ignored.
+    pub fn board(&self, entries: &[i8]) -> Grid {
+        let mut board = self.givens();
+        for (value, &entry) in board.iter_mut().zip(entries) {
+            if *value == 0 && entry > 0 {
+                *value = entry as u8;
+            }
+        }
+        board
+    }
+
+    /// Checks that the geometry, grid and every rule fit together, so the
+    /// solver and the checks built on it can index cells freely. Puzzles
+    /// from JS or a saved session must pass this first.
+    pub fn check(&self) -> Result<(), PuzzleError> {
+        if !self.geometry.is_valid() {
+            return Err(PuzzleError::InvalidGeometry);
+        }
+        let cells = self.geometry.cells();
+        if self.grid.len() != cells {
+            return Err(PuzzleError::GridLength { expected: cells, found: self.grid.len() });
+        }
+        self.check_entries(&self.grid)?;
+        let rules = self.constraints.iter().map(|&ineq| Constraint::from(ineq))
+            .chain(self.cages.iter().cloned().map(Constraint::from))
+            .chain(self.dots.iter().map(|&dot| Constraint::from(dot)))
+            .chain(self.thermos.iter().cloned().map(Constraint::from));
+        for rule in rules {
+            let mut seen = vec![false; cells];
+            for idx in rule.cells() {
+                if idx >= cells || seen[idx] {
+                    return Err(PuzzleError::InvalidCell(idx));
+                }
+                seen[idx] = true;
+            }
+        }
+        let geometry = &self.geometry;
+        let touching = |a: usize, b: usize| geometry.row(a).abs_diff(geometry.row(b)) <= 1 && geometry.col(a).abs_diff(geometry.col(b)) <= 1;
+        if let Some(index) = self.thermos.iter()
+            .position(|thermo| thermo.cells.len() < 2 || thermo.cells.windows(2).any(|pair| !touching(pair[0], pair[1])))
+        {
+            return Err(PuzzleError::InvalidThermo(index));
+        }
+        Ok(())
+    }
+
+    /// Checks that every value in `entries` (one per cell, 0 or -1 =
+    /// empty) is one the board can hold.
+    pub fn check_entries(&self, entries: &[i8]) -> Result<(), PuzzleError> {
+        let size = self.geometry.size as i8;
+        match entries.iter().position(|&value| !(-1..=size).contains(&value)) {
+            Some(cell) => Err(PuzzleError::InvalidValue { cell, value: entries[cell] }),
+            None => Ok(()),
+        }
+    }
+
+    /// The inequalities followed by one per thermometer segment, bulb side
+    /// smaller: the rules the logical solver and the candidate marks
+    /// reason with. Cages and dots are left to the search.
+    pub fn inequalities(&self) -> Vec<Inequality> {
+        let mut inequalities = self.constraints.clone();
+        for thermo in &self.thermos {
+            inequalities.extend(thermo.cells.windows(2).map(|pair| Inequality { a: pair[0], b: pair[1] }));
+        }
+        inequalities
+    }
+
+    /// Every inequality, cage, dot and thermometer, in the form the solver
+    /// takes. With `all_dots`, each undotted neighbour pair adds a `NoDot`.
+    pub fn rules(&self) -> Vec<Constraint> {
+        let mut rules: Vec<Constraint> = self.constraints.iter().map(|&ineq| Constraint::from(ineq)).collect();
+        rules.extend(self.cages.iter().cloned().map(Constraint::from));
+        rules.extend(self.dots.iter().map(|&dot| Constraint::from(dot)));
+        if self.all_dots {
+            let dotted = |a: usize, b: usize| self.dots.iter().any(|dot| (dot.a, dot.b) == (a, b) || (dot.a, dot.b) == (b, a));
+            rules.extend(self.geometry.neighbour_pairs()
+                .filter(|&(a, b)| !dotted(a, b))
+                .map(|(a, b)| Constraint::NoDot { a, b }));
+        }
+        rules.extend(self.thermos.iter().cloned().map(Constraint::from));
+        rules
+    }
 }
 
 /// Generates all valid inequality constraints for the given grid.
 /// Always generates 'a < b' form.
-pub fn generate_from_grid(grid: &Grid) -> Vec<Inequality> {
-    let mut constraints = Vec::new();
-
-    // Check horizontal neighbors (i, i+1)
-    for row in 0..9 {
-        for col in 0..8 {
-            let idx_a = row * 9 + col;
-            let idx_b = row * 9 + col + 1;
-            let val_a = grid[idx_a];
-            let val_b = grid[idx_b];
-            
-            if val_a < val_b {
-                constraints.push(Inequality { a: idx_a, b: idx_b });
-            } else {
-                constraints.push(Inequality { a: idx_b, b: idx_a });
-            }
+pub fn generate_from_grid(geometry: &Geometry, grid: &Grid) -> Vec<Inequality> {
+    geometry.neighbour_pairs()
+        .map(|(a, b)| if grid[a] < grid[b] { Inequality { a, b } } else { Inequality { a: b, b: a } })
+        .collect()
+}
+
+/// Marks every neighbour pair of the completed `grid` that qualifies for a
+/// Kropki dot.
+pub fn generate_dots_from_grid(geometry: &Geometry, grid: &Grid) -> Vec<Dot> {
+    geometry.neighbour_pairs()
+        .filter_map(|(a, b)| DotKind::between(grid[a], grid[b]).map(|kind| Dot { a, b, kind }))
+        .collect()
+}
+
+/// Splits the completed `grid` into cages of orthogonally connected cells
+/// with no repeated value, each of up to `max_size` cells. Every cell ends
+/// up in exactly one cage; a cell that cannot join a neighbour's cage gets
+/// one of its own.
+pub fn generate_cages_from_grid(geometry: &Geometry, grid: &Grid, max_size: usize, rng: &mut impl Rng) -> Vec<Cage> {
+    let n = geometry.size;
+    let mut caged = vec![false; geometry.cells()];
+    let mut order: Vec<usize> = (0..geometry.cells()).collect();
+    order.shuffle(rng);
+
+    let mut cages = Vec::new();
+    for start in order {
+        if caged[start] {
+            continue;
         }
+        caged[start] = true;
+        let target = rng.gen_range(1..=max_size.max(1));
+        let mut cells = vec![start];
+        while cells.len() < target {
+            let mut frontier: Vec<usize> = cells.iter()
+                .flat_map(|&idx| {
+                    let (r, c) = (geometry.row(idx), geometry.col(idx));
+                    [
+                        (r > 0).then(|| idx - n),
+                        (r + 1 < n).then(|| idx + n),
+                        (c > 0).then(|| idx - 1),
+                        (c + 1 < n).then(|| idx + 1),
+                    ]
+                })
+                .flatten()
+                .filter(|&j| !caged[j] && cells.iter().all(|&k| grid[k] != grid[j]))
+                .collect();
+            frontier.sort_unstable();
+            frontier.dedup();
+            let Some(&next) = frontier.choose(rng) else {
+                break;
+            };
+            caged[next] = true;
+            cells.push(next);
+        }
+        cells.sort_unstable();
+        let sum = cells.iter().map(|&idx| grid[idx] as u32).sum();
+        cages.push(Cage { cells, sum });
+    }
+    cages
+}
+
+/// Thermometers shorter than this say little more than an inequality.
+pub const MIN_THERMO_LENGTH: usize = 3;
+
+/// Grows up to `count` thermometers along increasing paths of the completed
+/// `grid`, each at least `MIN_THERMO_LENGTH` cells and none sharing a cell.
+/// A path starts at a random free cell and steps to a free neighbour,
+/// diagonals included, holding the next larger value available, so that
+/// it climbs slowly and grows long.
+pub fn generate_thermos_from_grid(geometry: &Geometry, grid: &Grid, count: usize, rng: &mut impl Rng) -> Vec<Thermo> {
+    let n = geometry.size as isize;
+    let mut used = vec![false; geometry.cells()];
+    let mut order: Vec<usize> = (0..geometry.cells()).collect();
+    order.shuffle(rng);
+
+    let mut thermos = Vec::new();
+    for start in order {
+        if thermos.len() == count {
+            break;
+        }
+        if used[start] {
+            continue;
+        }
+        let mut cells = vec![start];
+        loop {
+            let last = cells[cells.len() - 1];
+            let (r, c) = (geometry.row(last) as isize, geometry.col(last) as isize);
+            let next: Vec<usize> = (-1..=1).flat_map(|dr| (-1..=1).map(move |dc| (r + dr, c + dc)))
+                .filter(|&(r, c)| (0..n).contains(&r) && (0..n).contains(&c))
+                .map(|(r, c)| (r * n + c) as usize)
+                .filter(|&j| !used[j] && grid[j] > grid[last])
+                .collect();
+            let Some(step) = next.iter().map(|&j| grid[j]).min() else {
+                break;
+            };
+            let next: Vec<usize> = next.into_iter().filter(|&j| grid[j] == step).collect();
+            let Some(&next) = next.choose(rng) else {
+                break;
+            };
+            cells.push(next);
+        }
+        if cells.len() >= MIN_THERMO_LENGTH {
+            cells.iter().for_each(|&idx| used[idx] = true);
+            thermos.push(Thermo { cells });
+        }
+    }
+    thermos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_cages_partition_the_grid() {
+        let geometry = Geometry::classic();
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let grid = sudoku::generate_complete_grid(&geometry, &mut rng).unwrap();
+        let cages = generate_cages_from_grid(&geometry, &grid, 4, &mut rng);
+
+        let mut cells: Vec<usize> = cages.iter().flat_map(|cage| cage.cells.iter().copied()).collect();
+        cells.sort_unstable();
+        assert_eq!(cells, (0..81).collect::<Vec<_>>());
+        for cage in &cages {
+            assert!(cage.cells.len() <= 4);
+            assert_eq!(cage.sum, cage.cells.iter().map(|&i| grid[i] as u32).sum::<u32>());
+            let mut values: Vec<u8> = cage.cells.iter().map(|&i| grid[i]).collect();
+            values.sort_unstable();
+            values.dedup();
+            assert_eq!(values.len(), cage.cells.len(), "{:?} repeats a value", cage);
+        }
+    }
+
+    #[test]
+    fn test_dots_and_their_absence() {
+        let geometry = Geometry::latin(3);
+        let grid = vec![1, 2, 3, 3, 1, 2, 2, 3, 1];
+        let dots = generate_dots_from_grid(&geometry, &grid);
+        assert_eq!(dots[0], Dot { a: 0, b: 1, kind: DotKind::Black });
+        assert_eq!(dots[1], Dot { a: 1, b: 2, kind: DotKind::White });
+        assert!(dots.iter().all(|dot| dot.kind.holds(grid[dot.a], grid[dot.b])));
+        // Only the pairs holding 1 and 3 go without a dot.
+        let undotted = [(3, 4), (7, 8), (0, 3), (4, 7)];
+        assert_eq!(dots.len(), 12 - undotted.len());
+
+        let puzzle = Puzzle { geometry, grid: vec![-1; 9], dots, all_dots: true, ..Puzzle::default() };
+        let no_dots: Vec<(usize, usize)> = puzzle.rules().into_iter()
+            .filter_map(|rule| match rule {
+                Constraint::NoDot { a, b } => Some((a, b)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(no_dots.len(), undotted.len());
+        assert!(undotted.iter().all(|pair| no_dots.contains(pair)));
     }
 
-    // Check vertical neighbors (i, i+9)
-    for row in 0..8 {
-        for col in 0..9 {
-            let idx_a = row * 9 + col;
-            let idx_b = (row + 1) * 9 + col;
-            let val_a = grid[idx_a];
-            let val_b = grid[idx_b];
+    #[test]
+    fn test_thermos_follow_increasing_paths() {
+        let geometry = Geometry::classic();
+        let mut rng = ChaCha8Rng::seed_from_u64(6);
+        let grid = sudoku::generate_complete_grid(&geometry, &mut rng).unwrap();
+        let thermos = generate_thermos_from_grid(&geometry, &grid, 8, &mut rng);
+        assert_eq!(thermos.len(), 8);
 
-            if val_a < val_b {
-                constraints.push(Inequality { a: idx_a, b: idx_b });
-            } else {
-                constraints.push(Inequality { a: idx_b, b: idx_a });
+        let mut used = [false; 81];
+        for thermo in &thermos {
+            assert!(thermo.cells.len() >= MIN_THERMO_LENGTH);
+            for pair in thermo.cells.windows(2) {
+                assert!(grid[pair[0]] < grid[pair[1]]);
+                assert!(geometry.row(pair[0]).abs_diff(geometry.row(pair[1])) <= 1);
+                assert!(geometry.col(pair[0]).abs_diff(geometry.col(pair[1])) <= 1);
+            }
+            for &idx in &thermo.cells {
+                assert!(!used[idx], "cell {} is on two thermometers", idx);
+                used[idx] = true;
             }
         }
     }
 
-    constraints
+    #[test]
+    fn test_check_rejects_malformed_puzzles() {
+        let geometry = Geometry::latin(4);
+        let puzzle = Puzzle { geometry: geometry.clone(), grid: vec![-1; 16], ..Puzzle::default() };
+        assert_eq!(puzzle.check(), Ok(()));
+
+        let with = |change: fn(&mut Puzzle)| {
+            let mut puzzle = puzzle.clone();
+            change(&mut puzzle);
+            puzzle.check()
+        };
+        assert_eq!(with(|p| p.grid.truncate(15)), Err(PuzzleError::GridLength { expected: 16, found: 15 }));
+        assert_eq!(with(|p| p.grid[3] = 5), Err(PuzzleError::InvalidValue { cell: 3, value: 5 }));
+        assert_eq!(with(|p| p.constraints.push(Inequality { a: 0, b: 99 })), Err(PuzzleError::InvalidCell(99)));
+        assert_eq!(with(|p| p.cages.push(Cage { cells: vec![1, 1], sum: 3 })), Err(PuzzleError::InvalidCell(1)));
+        assert_eq!(with(|p| p.thermos.push(Thermo { cells: vec![0, 16] })), Err(PuzzleError::InvalidCell(16)));
+        assert_eq!(with(|p| p.thermos.push(Thermo { cells: vec![0, 5, 10] })), Ok(()));
+        assert_eq!(with(|p| p.thermos.push(Thermo { cells: Vec::new() })), Err(PuzzleError::InvalidThermo(0)));
+        assert_eq!(with(|p| p.thermos.push(Thermo { cells: vec![6] })), Err(PuzzleError::InvalidThermo(0)));
+        assert_eq!(with(|p| p.thermos.push(Thermo { cells: vec![0, 1, 3] })), Err(PuzzleError::InvalidThermo(0)));
+        assert_eq!(with(|p| p.geometry.size = 13), Err(PuzzleError::InvalidGeometry));
+        assert_eq!(with(|p| p.geometry.jigsaw = Some(vec![0; 16])), Err(PuzzleError::InvalidGeometry));
+        assert_eq!(with(|p| p.geometry.regions = vec![vec![0, 1, 2, 40]]), Err(PuzzleError::InvalidGeometry));
+    }
+
+    #[test]
+    fn test_board_overlays_entries() {
+        let puzzle = Puzzle { geometry: Geometry::latin(2), grid: vec![1, -1, -1, -1], ..Puzzle::default() };
+        assert_eq!(puzzle.board(&[2, 2, -1, 0]), vec![1, 2, 0, 0]);
+        assert_eq!(puzzle.board(&[]), vec![1, 0, 0, 0]);
+    }
 }
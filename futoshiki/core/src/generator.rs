@@ -1,7 +1,14 @@
 use crate::sudoku;
-use crate::futoshiki::{self, Puzzle, Inequality};
-use crate::solver;
+use crate::futoshiki::{self, Constraint, Puzzle, Inequality};
+use crate::geometry::{Geometry, RegionSet};
+use crate::solver::{self, SolverStats};
+use crate::logic::Technique;
+use crate::rating::{self, Rating, RatingBand};
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
 pub enum Difficulty {
     Easy,
@@ -9,17 +16,166 @@ pub enum Difficulty {
     Hard,
     Expert,
     Classic,  // 블록 내 부등호만, 숫자 힌트 없음
+    // 순수 Futoshiki (N×N 라틴 방진, 블록 없음)
+    LatinEasy,
+    LatinNormal,
+    LatinHard,
 }
 
-impl Difficulty {
-    pub fn from_str(s: &str) -> Self {
+/// 라틴 모드 기본 크기와 허용 범위
+pub const LATIN_DEFAULT_SIZE: usize = 5;
+pub const LATIN_SIZES: std::ops::RangeInclusive<usize> = 4..=9;
+
+/// 숫자 힌트를 놓는 대칭. 대칭이 있으면 힌트를 대칭 궤도 단위로 공개하고 지운다.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Symmetry {
+    #[default]
+    None,
+    /// 180° 회전
+    Rotational,
+    /// 가로 중심선 기준 위아래 대칭 (행 r ↔ n-1-r)
+    Horizontal,
+    /// 세로 중심선 기준 좌우 대칭 (열 c ↔ n-1-c)
+    Vertical,
+    /// 주대각선 기준 대칭 ((r, c) ↔ (c, r))
+    Diagonal,
+}
+
+impl Symmetry {
+    /// 대칭으로 `idx`와 짝이 되는 칸
+    pub fn image(&self, geometry: &Geometry, idx: usize) -> usize {
+        let n = geometry.size;
+        let (r, c) = (geometry.row(idx), geometry.col(idx));
+        match self {
+            Symmetry::None => idx,
+            Symmetry::Rotational => geometry.cells() - 1 - idx,
+            Symmetry::Horizontal => (n - 1 - r) * n + c,
+            Symmetry::Vertical => r * n + (n - 1 - c),
+            Symmetry::Diagonal => c * n + r,
+        }
+    }
+
+    /// 칸을 대칭 궤도로 묶는다. 궤도는 한 칸(대칭축 위) 또는 두 칸이다.
+    pub fn orbits(&self, geometry: &Geometry) -> Vec<Vec<usize>> {
+        (0..geometry.cells())
+            .filter_map(|idx| match self.image(geometry, idx) {
+                image if image == idx => Some(vec![idx]),
+                image if image > idx => Some(vec![idx, image]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 부등호를 대칭 궤도로 묶는다. 짝이 되는 칸 쌍의 부등호가 목록에 없으면 혼자 남는다.
+    fn inequality_orbits(&self, geometry: &Geometry, inequalities: &[Inequality]) -> Vec<Vec<Inequality>> {
+        let pair = |a: usize, b: usize| (a.min(b), a.max(b));
+        let mut used = vec![false; inequalities.len()];
+        let mut orbits = Vec::new();
+        for (k, &iq) in inequalities.iter().enumerate() {
+            if used[k] {
+                continue;
+            }
+            used[k] = true;
+            let mut orbit = vec![iq];
+            let image = pair(self.image(geometry, iq.a), self.image(geometry, iq.b));
+            if let Some(j) = (0..inequalities.len()).find(|&j| !used[j] && pair(inequalities[j].a, inequalities[j].b) == image) {
+                used[j] = true;
+                orbit.push(inequalities[j]);
+            }
+            orbits.push(orbit);
+        }
+        orbits
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "Easy" => Difficulty::Easy,
-            "Normal" => Difficulty::Normal,
-            "Hard" => Difficulty::Hard,
-            "Expert" => Difficulty::Expert,
-            "Classic" => Difficulty::Classic,
-            _ => Difficulty::Normal,
+            "None" => Ok(Symmetry::None),
+            "Rotational" => Ok(Symmetry::Rotational),
+            "Horizontal" => Ok(Symmetry::Horizontal),
+            "Vertical" => Ok(Symmetry::Vertical),
+            "Diagonal" => Ok(Symmetry::Diagonal),
+            _ => Err(format!("unknown symmetry \"{}\"", s)),
+        }
+    }
+}
+
+/// 생성 실패 원인. wasm에서는 JS 예외로 전달된다.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GenerateError {
+    /// 모든 시도에서 조건에 맞는 퍼즐을 만들지 못함
+    AttemptsExhausted { attempts: usize },
+    InvalidDifficulty(String),
+    /// 결과를 JS 값이나 JSON으로 바꾸지 못함
+    Serialization(String),
+    /// 추가 영역이 보드에 맞지 않음 (크기가 다르거나, 블록 없는 보드의 Windoku거나,
+    /// 영역끼리 모순이라 채울 수 없음)
+    InvalidRegions,
+    /// 진행 상황 콜백이 중단을 요청함
+    Cancelled,
+    /// 시간 또는 노드 예산을 다 씀
+    BudgetExceeded,
+    /// 이 난이도가 지원하지 않는 보드 크기
+    UnsupportedSize(usize),
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerateError::AttemptsExhausted { attempts } => write!(f, "no valid puzzle found after {} attempts", attempts),
+            GenerateError::InvalidDifficulty(name) => write!(f, "unknown difficulty \"{}\"", name),
+            GenerateError::Serialization(msg) => write!(f, "failed to serialize puzzle: {}", msg),
+            GenerateError::InvalidRegions => write!(f, "extra regions do not fit the board"),
+            GenerateError::Cancelled => write!(f, "generation cancelled"),
+            GenerateError::BudgetExceeded => write!(f, "generation budget exceeded"),
+            GenerateError::UnsupportedSize(size) => write!(f, "unsupported board size {}", size),
+        }
+    }
+}
+
+impl std::error::Error for GenerateError {}
+
+impl FromStr for Difficulty {
+    type Err = GenerateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Easy" => Ok(Difficulty::Easy),
+            "Normal" => Ok(Difficulty::Normal),
+            "Hard" => Ok(Difficulty::Hard),
+            "Expert" => Ok(Difficulty::Expert),
+            "Classic" => Ok(Difficulty::Classic),
+            "LatinEasy" => Ok(Difficulty::LatinEasy),
+            "LatinNormal" => Ok(Difficulty::LatinNormal),
+            "LatinHard" => Ok(Difficulty::LatinHard),
+            _ => Err(GenerateError::InvalidDifficulty(s.to_string())),
+        }
+    }
+}
+
+impl Difficulty {
+    pub fn is_latin(&self) -> bool {
+        matches!(self, Difficulty::LatinEasy | Difficulty::LatinNormal | Difficulty::LatinHard)
+    }
+
+    /// 보드 모양. 크기를 주지 않으면 라틴 모드는 기본 크기, 블록 모드는
+    /// 9x9다. 라틴 모드는 4~9, 블록 모드는 4/6/9/12만 지원한다.
+    pub fn geometry(&self, size: Option<usize>) -> Result<Geometry, GenerateError> {
+        if self.is_latin() {
+            let size = size.unwrap_or(LATIN_DEFAULT_SIZE);
+            if !LATIN_SIZES.contains(&size) {
+                return Err(GenerateError::UnsupportedSize(size));
+            }
+            return Ok(Geometry::latin(size));
+        }
+        match size {
+            None => Ok(Geometry::classic()),
+            // Classic은 숫자 힌트 없이 유일성을 검사해야 해서 12x12는 너무 오래 걸린다
+            Some(n) if matches!(self, Difficulty::Classic) && n > 9 => Err(GenerateError::UnsupportedSize(n)),
+            Some(n) => Geometry::boxed(n).ok_or(GenerateError::UnsupportedSize(n)),
         }
     }
 
@@ -31,10 +187,14 @@ impl Difficulty {
             Difficulty::Hard => 0.35,
             Difficulty::Expert => 0.2,
             Difficulty::Classic => 1.0,  // 모든 부등호 제공 (숫자 힌트 없음)
+            // 라틴 모드는 블록이 없으므로 모든 부등호가 이 비율을 따른다
+            Difficulty::LatinEasy => 0.6,
+            Difficulty::LatinNormal => 0.55,
+            Difficulty::LatinHard => 0.5,
         }
     }
 
-    /// 허용되는 힌트 수 범위 (min, max)
+    /// 허용되는 힌트 수 범위 (min, max), 81칸 기준
     pub fn hint_range(&self) -> (usize, usize) {
         match self {
             Difficulty::Easy => (30, 40),
@@ -42,10 +202,13 @@ impl Difficulty {
             Difficulty::Hard => (16, 22),
             Difficulty::Expert => (8, 15),
             Difficulty::Classic => (0, 0),  // 숫자 힌트 없음
+            Difficulty::LatinEasy => (8, 24),
+            Difficulty::LatinNormal => (6, 20),
+            Difficulty::LatinHard => (2, 14),
         }
     }
 
-    /// 초기에 무조건 공개할 셀 수
+    /// 초기에 무조건 공개할 셀 수, 81칸 기준
     pub fn initial_reveals(&self) -> usize {
         match self {
             Difficulty::Easy => 20,
@@ -53,125 +216,604 @@ impl Difficulty {
             Difficulty::Hard => 6,
             Difficulty::Expert => 0,
             Difficulty::Classic => 0,
+            Difficulty::LatinEasy => 8,
+            Difficulty::LatinNormal => 6,
+            Difficulty::LatinHard => 2,
         }
     }
+
+    /// `geometry`에 맞춘 허용 힌트 수 범위 (min, max)와 초기 공개 수.
+    /// 81칸 기준 값을 칸 수로 환산하지만, 블록 없는 라틴 보드는 7x7부터 유일 해에
+    /// 필요한 힌트가 칸 수보다 빠르게 늘어 환산 범위에 닿지 않으므로 크기별 값을 쓴다.
+    /// 4x4 Expert는 환산한 최소 2개만 공개해도 평가 범위보다 쉬워지므로 0개부터 허용한다.
+    pub fn clue_presets(&self, geometry: &Geometry) -> ((usize, usize), usize) {
+        match (self, geometry.size) {
+            (Difficulty::Expert, 4) => ((0, 3), 0),
+            (Difficulty::LatinEasy, 7) => ((8, 30), 8),
+            (Difficulty::LatinNormal, 7) => ((6, 26), 6),
+            (Difficulty::LatinHard, 7) => ((4, 22), 4),
+            (Difficulty::LatinEasy, 8) => ((16, 40), 16),
+            (Difficulty::LatinNormal, 8) => ((12, 36), 12),
+            (Difficulty::LatinHard, 8) => ((8, 32), 8),
+            (Difficulty::LatinEasy, 9) => ((20, 48), 20),
+            (Difficulty::LatinNormal, 9) => ((16, 44), 16),
+            (Difficulty::LatinHard, 9) => ((12, 40), 12),
+            _ => {
+                let (min, max) = self.hint_range();
+                ((scale_to(geometry, min), scale_to(geometry, max)), scale_to(geometry, self.initial_reveals()))
+            }
+        }
+    }
+
+    /// 논리 풀이 기준 허용 평가 범위. 힌트 수만으로는 난이도가 뒤섞이므로 함께 검사한다.
+    pub fn rating_band(&self) -> RatingBand {
+        match self {
+            Difficulty::Easy => RatingBand {
+                min_score: 0,
+                max_score: 100,
+                min_technique: Technique::NakedSingle,
+                max_technique: Technique::InequalityBound,
+                allow_guessing: false,
+            },
+            Difficulty::Normal => RatingBand {
+                min_score: 80,
+                max_score: 170,
+                min_technique: Technique::NakedSingle,
+                max_technique: Technique::HiddenSubset,
+                allow_guessing: false,
+            },
+            Difficulty::Hard => RatingBand {
+                min_score: 140,
+                max_score: 300,
+                min_technique: Technique::InequalityBound,
+                max_technique: Technique::HiddenSubset,
+                allow_guessing: false,
+            },
+            Difficulty::Expert => RatingBand {
+                min_score: 220,
+                max_score: u32::MAX,
+                min_technique: Technique::InequalityBound,
+                max_technique: Technique::HiddenSubset,
+                allow_guessing: true,
+            },
+            Difficulty::Classic => RatingBand {
+                min_score: 0,
+                max_score: u32::MAX,
+                min_technique: Technique::NakedSingle,
+                max_technique: Technique::HiddenSubset,
+                allow_guessing: true,
+            },
+            // 라틴 모드는 크기마다 점수 규모가 달라 기법만으로 구분한다
+            Difficulty::LatinEasy => RatingBand {
+                min_score: 0,
+                max_score: u32::MAX,
+                min_technique: Technique::NakedSingle,
+                max_technique: Technique::InequalityBound,
+                allow_guessing: false,
+            },
+            Difficulty::LatinNormal => RatingBand {
+                min_score: 0,
+                max_score: u32::MAX,
+                min_technique: Technique::InequalityBound,
+                max_technique: Technique::HiddenSubset,
+                allow_guessing: false,
+            },
+            Difficulty::LatinHard => RatingBand {
+                min_score: 0,
+                max_score: u32::MAX,
+                min_technique: Technique::InequalityBound,
+                max_technique: Technique::HiddenSubset,
+                allow_guessing: true,
+            },
+        }
+    }
+}
+
+/// 81칸 기준 개수를 실제 보드 칸 수에 맞춰 반올림 환산
+fn scale_to(geometry: &Geometry, count: usize) -> usize {
+    (count * geometry.cells() + 40) / 81
+}
+
+/// 평가 점수는 채울 칸 수에 비례하므로 점수 범위도 같은 비율로 환산.
+/// 작은 보드에서 범위가 너무 좁아지지 않도록 바깥쪽으로 반올림한다.
+fn scale_band(geometry: &Geometry, band: RatingBand) -> RatingBand {
+    let cells = geometry.cells() as u64;
+    let max_score = match band.max_score {
+        u32::MAX => u32::MAX,
+        max => ((max as u64 * cells).div_ceil(81)) as u32,
+    };
+    RatingBand {
+        min_score: (band.min_score as u64 * cells / 81) as u32,
+        max_score,
+        ..band
+    }
 }
 
 const MAX_ATTEMPTS: usize = 50;
 const CLASSIC_MAX_ATTEMPTS: usize = 1000;  // Classic은 더 많은 시도 필요
 
-pub fn generate(difficulty_str: &str) -> Puzzle {
-    let difficulty = Difficulty::from_str(difficulty_str);
-    let (min_hints, max_hints) = difficulty.hint_range();
-    let initial_reveals = difficulty.initial_reveals();
-    let mut rng = rand::thread_rng();
+/// 생성 예산. 둘 다 None이면 제한 없음.
+/// 시간은 유일성 검사와 직소 배치 사이에서 확인하므로 검사 하나가 길면 그만큼 넘길 수 있다.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Budget {
+    /// 전체 생성에 쓸 수 있는 시간 (ms)
+    pub max_millis: Option<f64>,
+    /// 모든 유일성 검사를 합친 탐색 노드 수
+    pub max_nodes: Option<u64>,
+}
+
+/// 난이도 기본값을 바꾸는 생성 옵션
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct GenerateOptions {
+    /// 보드 한 변의 길이 (None이면 기본값). 블록 모드는 4, 6, 9, 12 중 하나.
+    pub size: Option<usize>,
+    /// 유일 해를 만든 뒤 불필요한 숫자 힌트와 블록 간 부등호를 제거할지. 기본은 끈다.
+    /// 최소화한 퍼즐은 필요한 만큼만 힌트를 가지므로 최소 힌트 수를 검사하지 않는다.
+    pub minimize: bool,
+    /// 숫자 힌트 배치 대칭
+    pub symmetry: Symmetry,
+    /// 블록 간 부등호도 같은 대칭으로 남길지
+    pub symmetric_inequalities: bool,
+    /// 킬러 변형: Some(n)이면 완성 격자를 최대 n칸 케이지로 나눠 부등호와 함께 준다.
+    /// 논리 풀이 평가는 케이지를 모르므로 이때는 평가 범위를 검사하지 않는다.
+    pub max_cage_size: Option<usize>,
+    /// Kropki 변형: 조건에 맞는 모든 이웃 쌍에 흰 점(차이 1)과 검은 점(두 배)을 준다.
+    /// 케이지와 마찬가지로 평가 범위를 검사하지 않는다.
+    pub dots: bool,
+    /// 모든 점을 표시했다고 알려 점이 없는 쌍도 단서가 되게 할지 (`dots`일 때만)
+    pub all_dots: bool,
+    /// 온도계 변형: 완성 격자의 증가 경로를 따라 만들 온도계 수 (0이면 없음).
+    /// 논리 풀이는 온도계를 부등호 사슬로 보므로 평가 범위도 검사한다.
+    pub thermometers: usize,
+    /// 행, 열, 블록 외에 숫자가 겹치면 안 되는 추가 영역 (대각선, Windoku, 직접 지정)
+    pub regions: Vec<RegionSet>,
+    /// 직소 변형: 블록을 무작위로 만든 연결된 불규칙 영역으로 바꾼다.
+    /// 블록 내/간 부등호 구분도 이 영역을 따른다.
+    pub jigsaw: bool,
+}
+
+impl GenerateOptions {
+    /// 논리 풀이 평가가 모르는 변형 규칙(케이지, 점)을 쓰는지
+    fn has_variant_rules(&self) -> bool {
+        self.max_cage_size.is_some() || self.dots
+    }
+}
+
+/// 시도가 끝날 때마다, 그리고 직소 배치나 힌트 최소화 중에는
+/// `PROGRESS_INTERVAL_MS`마다 진행 상황 콜백에 넘기는 값
+#[derive(Debug, Clone, Serialize)]
+pub struct Progress<'a> {
+    /// 끝난 시도 수. 첫 시도가 끝나기 전의 보고에서는 0
+    pub attempt: usize,
+    pub max_attempts: usize,
+    pub elapsed_ms: f64,
+    /// 지금까지 쓴 탐색 노드 수
+    pub nodes: u64,
+    /// 유일 해지만 평가가 범위를 벗어나 버린 후보 중 범위에 가장 가까운 것
+    pub best: Option<&'a Candidate>,
+}
+
+/// 생성된 퍼즐과 그 유일성을 확인한 마지막 탐색의 통계.
+/// 통계를 플레이어 풀이 시간과 비교해 난이도를 조정한다.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Candidate {
+    pub puzzle: Puzzle,
+    pub stats: SolverStats,
+    /// 시도를 다 써서 힌트 범위와 평가 범위 없이 만든 퍼즐
+    pub fallback: bool,
+}
+
+/// 진행 상황 콜백을 다시 부르기까지의 최소 간격 (ms).
+/// 직소 배치와 힌트 최소화는 시도 하나 안에서도 이 간격으로 취소를 확인한다.
+const PROGRESS_INTERVAL_MS: f64 = 100.0;
+
+/// 예산 사용량과 진행 상황 추적
+struct Meter<'a> {
+    budget: Budget,
+    started: f64,
+    nodes: u64,
+    /// 끝난 시도 수
+    attempt: usize,
+    max_attempts: usize,
+    /// 평가 범위에 가장 가까운 후보와 그 거리
+    best: Option<(Candidate, (usize, u32))>,
+    on_progress: &'a mut dyn FnMut(&Progress) -> bool,
+    last_report: f64,
+}
+
+impl<'a> Meter<'a> {
+    fn new(budget: Budget, max_attempts: usize, on_progress: &'a mut dyn FnMut(&Progress) -> bool) -> Self {
+        let started = now_ms();
+        Meter { budget, started, nodes: 0, attempt: 0, max_attempts, best: None, on_progress, last_report: started }
+    }
+
+    fn elapsed_ms(&self) -> f64 {
+        now_ms() - self.started
+    }
+
+    fn exhausted(&self) -> bool {
+        self.budget.max_millis.is_some_and(|ms| self.elapsed_ms() >= ms)
+            || self.budget.max_nodes.is_some_and(|nodes| self.nodes >= nodes)
+    }
+
+    /// 콜백에 진행 상황을 알린다. 콜백이 false를 돌려주면 `Cancelled`
+    fn report(&mut self) -> Result<(), GenerateError> {
+        self.last_report = now_ms();
+        let progress = Progress {
+            attempt: self.attempt,
+            max_attempts: self.max_attempts,
+            elapsed_ms: self.last_report - self.started,
+            nodes: self.nodes,
+            best: self.best.as_ref().map(|(candidate, _)| candidate),
+        };
+        if (self.on_progress)(&progress) {
+            Ok(())
+        } else {
+            Err(GenerateError::Cancelled)
+        }
+    }
+
+    /// 오래 걸리는 단계에서 틈틈이 부른다. 예산이 바닥났으면 `BudgetExceeded`,
+    /// 마지막 보고 뒤로 `PROGRESS_INTERVAL_MS`가 지났으면 다시 보고한다.
+    fn check(&mut self) -> Result<(), GenerateError> {
+        if self.exhausted() {
+            return Err(GenerateError::BudgetExceeded);
+        }
+        if now_ms() - self.last_report >= PROGRESS_INTERVAL_MS {
+            self.report()?;
+        }
+        Ok(())
+    }
+
+    /// 해 개수 (최대 2)와 탐색 통계. 예산이 바닥나면 `BudgetExceeded`
+    fn count_solutions(&mut self, geometry: &Geometry, grid: &sudoku::Grid, constraints: &[Constraint]) -> Result<(usize, SolverStats), GenerateError> {
+        if self.exhausted() {
+            return Err(GenerateError::BudgetExceeded);
+        }
+        let allowance = self.budget.max_nodes.map_or(u64::MAX, |nodes| nodes - self.nodes);
+        match solver::count_solutions_within(geometry, grid, constraints, 2, allowance) {
+            Some((count, stats)) => {
+                self.nodes += stats.nodes;
+                Ok((count, stats))
+            }
+            None => {
+                self.nodes += allowance;
+                Err(GenerateError::BudgetExceeded)
+            }
+        }
+    }
+}
+
+/// 현재 시각 (ms). wasm32에는 `std::time` 시계가 없어 JS의 `Date.now()`를 쓴다.
+#[cfg(target_arch = "wasm32")]
+fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
 
+#[cfg(not(target_arch = "wasm32"))]
+fn now_ms() -> f64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64() * 1000.0)
+}
+
+/// 한 번의 시도 결과
+enum Attempt {
+    Accepted(Candidate),
+    /// 버린 시도. 유일 해지만 평가가 범위 밖이면 그 후보와 평가를 함께 돌려준다.
+    Rejected(Option<(Candidate, Rating)>),
+    /// 예산이 바닥났거나 취소됨
+    Stopped(GenerateError),
+}
+
+pub fn generate(difficulty_str: &str) -> Result<Puzzle, GenerateError> {
+    generate_with_rng(difficulty_str, None, &mut rand::thread_rng())
+}
+
+/// 시드 기반 생성: 같은 시드와 난이도는 항상 같은 퍼즐을 만든다.
+/// ChaCha8은 플랫폼이나 rand 버전과 무관하게 같은 수열을 낸다.
+pub fn generate_seeded(difficulty_str: &str, seed: u64) -> Result<Puzzle, GenerateError> {
+    generate_with_rng(difficulty_str, None, &mut ChaCha8Rng::seed_from_u64(seed))
+}
+
+/// `size`는 보드 한 변의 길이 (None이면 기본값). 블록 모드는 4, 6, 9, 12 중 하나.
+pub fn generate_with_rng(difficulty_str: &str, size: Option<usize>, rng: &mut impl Rng) -> Result<Puzzle, GenerateError> {
+    let options = GenerateOptions { size, ..GenerateOptions::default() };
+    generate_with_budget(difficulty_str, &options, rng, Budget::default(), |_| true).map(|candidate| candidate.puzzle)
+}
+
+/// 예산 안에서 생성하고 시도마다 `on_progress`를 부른다.
+/// 콜백이 false를 돌려주면 `Cancelled`로 멈춘다. 같은 RNG 상태면 예산이
+/// 충분한 한 `generate_with_rng`와 같은 퍼즐을 만든다.
+pub fn generate_with_budget(
+    difficulty_str: &str,
+    options: &GenerateOptions,
+    rng: &mut impl Rng,
+    budget: Budget,
+    mut on_progress: impl FnMut(&Progress) -> bool,
+) -> Result<Candidate, GenerateError> {
+    let difficulty: Difficulty = difficulty_str.parse()?;
     let max_tries = match difficulty {
         Difficulty::Classic => CLASSIC_MAX_ATTEMPTS,
         _ => MAX_ATTEMPTS,
     };
+    let mut meter = Meter::new(budget, max_tries, &mut on_progress);
+
+    let mut geometry = difficulty.geometry(options.size)?;
+    if options.jigsaw {
+        // 직소 블록은 아래에서 정하므로 블록 없는 보드에 추가 영역부터 얹는다
+        geometry = Geometry::latin(geometry.size);
+    }
+    for set in &options.regions {
+        let regions = set.regions(&geometry).ok_or(GenerateError::InvalidRegions)?;
+        geometry = geometry.with_regions(regions).ok_or(GenerateError::InvalidRegions)?;
+    }
+    // 추가 영역끼리 겹치면 채울 수 있는 격자가 없을 수 있다
+    if options.jigsaw {
+        geometry = jigsaw_geometry(&geometry, &mut meter, rng)?;
+    } else if !geometry.regions.is_empty() && !sudoku::is_fillable(&geometry) {
+        return Err(GenerateError::InvalidRegions);
+    }
+    let minimize = options.minimize;
+    let ((min_hints, max_hints), initial_reveals) = difficulty.clue_presets(&geometry);
+    let band = scale_band(&geometry, difficulty.rating_band());
+    let band = (!options.has_variant_rules()).then_some(&band);
 
-    for _ in 0..max_tries {
-        if let Some(puzzle) = try_generate(&difficulty, min_hints, max_hints, initial_reveals, &mut rng) {
-            return puzzle;
+    for attempt in 1..=max_tries {
+        match try_generate(&difficulty, &geometry, min_hints, max_hints, initial_reveals, minimize, options, band, &mut meter, rng) {
+            Attempt::Accepted(candidate) => return Ok(candidate),
+            Attempt::Rejected(Some((candidate, rating))) => {
+                let distance = band.map_or((0, 0), |band| band.distance(&rating));
+                if meter.best.as_ref().is_none_or(|(_, best_distance)| distance < *best_distance) {
+                    meter.best = Some((candidate, distance));
+                }
+            }
+            Attempt::Rejected(None) => {}
+            Attempt::Stopped(err) => return Err(err),
         }
+        meter.attempt = attempt;
+        meter.report()?;
+    }
+
+    // Fallback: Classic은 fallback 없이 실패 (유일 해 필수)
+    if matches!(difficulty, Difficulty::Classic) {
+        return Err(GenerateError::AttemptsExhausted { attempts: max_tries });
     }
+    match try_generate(&difficulty, &geometry, 0, geometry.cells(), initial_reveals, minimize, options, None, &mut meter, rng) {
+        Attempt::Accepted(candidate) => Ok(Candidate { fallback: true, ..candidate }),
+        Attempt::Rejected(_) => Err(GenerateError::AttemptsExhausted { attempts: max_tries }),
+        Attempt::Stopped(err) => Err(err),
+    }
+}
+
+/// 직소 배치를 시도하는 횟수
+const JIGSAW_LAYOUTS: usize = 100;
 
-    // Fallback: Classic은 fallback 없이 panic (유일 해 필수)
-    match difficulty {
-        Difficulty::Classic => panic!("Failed to generate Classic puzzle with unique solution"),
-        _ => try_generate(&difficulty, 0, 81, initial_reveals, &mut rng)
-            .expect("Failed to generate puzzle"),
+/// `base`의 추가 영역을 그대로 둔 무작위 직소 보드. 풀이기가 금방 채우지 못하는
+/// 배치는 버리고 다시 만든다. 배치마다 예산과 취소를 확인한다.
+fn jigsaw_geometry(base: &Geometry, meter: &mut Meter, rng: &mut impl Rng) -> Result<Geometry, GenerateError> {
+    for _ in 0..JIGSAW_LAYOUTS {
+        meter.check()?;
+        let geometry = sudoku::random_jigsaw_layout(base, rng);
+        if sudoku::is_fillable(&geometry) {
+            return Ok(geometry);
+        }
     }
+    // 추가 영역이 모순이면 어떤 배치도 채울 수 없다
+    Err(GenerateError::InvalidRegions)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn try_generate(
     difficulty: &Difficulty,
+    geometry: &Geometry,
     min_hints: usize,
     max_hints: usize,
     initial_reveals: usize,
+    minimize: bool,
+    options: &GenerateOptions,
+    band: Option<&RatingBand>,
+    meter: &mut Meter,
     rng: &mut impl Rng,
-) -> Option<Puzzle> {
+) -> Attempt {
     // 1. Generate full valid grid
-    let full_grid = sudoku::generate_complete_grid();
+    let Some(full_grid) = sudoku::generate_complete_grid(geometry, rng) else {
+        return Attempt::Rejected(None);
+    };
 
     // 2. Generate ALL inequalities
-    let all_inequalities = futoshiki::generate_from_grid(&full_grid);
+    let all_inequalities = futoshiki::generate_from_grid(geometry, &full_grid);
 
     // 3. Separate Fixed (Intra) and Variable (Inter)
-    let (fixed, mut variable): (Vec<Inequality>, Vec<Inequality>) =
-        all_inequalities.into_iter().partition(|iq| iq.is_intra_block());
+    let (fixed, variable): (Vec<Inequality>, Vec<Inequality>) =
+        all_inequalities.into_iter().partition(|iq| iq.is_intra_block(geometry));
 
     // 4. Select Variable inequalities based on difficulty
-    variable.shuffle(rng);
+    // 대칭이면 궤도 단위로 골라 유지 수에 닿을 때까지 남긴다
     let keep_count = (variable.len() as f64 * difficulty.inter_block_keep_ratio()) as usize;
-    let selected_variable: Vec<Inequality> = variable.into_iter().take(keep_count).collect();
+    let inequality_symmetry = if options.symmetric_inequalities { options.symmetry } else { Symmetry::None };
+    let mut inequality_orbits = inequality_symmetry.inequality_orbits(geometry, &variable);
+    inequality_orbits.shuffle(rng);
+    let mut kept = 0;
+    inequality_orbits.retain(|orbit| {
+        let keep = kept < keep_count;
+        if keep {
+            kept += orbit.len();
+        }
+        keep
+    });
 
-    let mut constraints = fixed;
-    constraints.extend(selected_variable);
+    let mut constraints: Vec<Constraint> = fixed.into_iter().map(Constraint::from).collect();
+    constraints.extend(inequality_orbits.iter().flatten().map(|&iq| Constraint::from(iq)));
+    if let Some(max_size) = options.max_cage_size {
+        let cages = futoshiki::generate_cages_from_grid(geometry, &full_grid, max_size, rng);
+        constraints.extend(cages.into_iter().map(Constraint::from));
+    }
+    if options.dots {
+        let kropki = Puzzle {
+            geometry: geometry.clone(),
+            dots: futoshiki::generate_dots_from_grid(geometry, &full_grid),
+            all_dots: options.all_dots,
+            ..Puzzle::default()
+        };
+        constraints.extend(kropki.rules());
+    }
+    if options.thermometers > 0 {
+        let thermos = futoshiki::generate_thermos_from_grid(geometry, &full_grid, options.thermometers, rng);
+        constraints.extend(thermos.into_iter().map(Constraint::from));
+    }
 
-    // 5. 초기 힌트 배치: 난이도별 초기 공개 수만큼 먼저 공개
-    let mut puzzle_grid = [0u8; 81];
-    let mut indices: Vec<usize> = (0..81).collect();
-    indices.shuffle(rng);
+    // 5. 초기 힌트 배치: 난이도별 초기 공개 수만큼 먼저 공개.
+    // 최소 힌트 수보다 적게 끝나는 퍼즐은 없으므로 거기까지 한 번에 공개해
+    // 큰 보드에서 힌트가 거의 없는 상태로 유일성을 검사하지 않게 한다.
+    // 공개는 대칭 궤도 단위이므로 목표 수를 한 칸 넘을 수 있다.
+    let cells = geometry.cells();
+    let mut puzzle_grid = vec![0u8; cells];
+    // 궤도 목록은 칸 순서로 두고 공개 순서만 섞는다 (대칭이 없을 때 기존 시드의 결과 유지)
+    let orbits = options.symmetry.orbits(geometry);
+    let mut reveal_order: Vec<&Vec<usize>> = orbits.iter().collect();
+    reveal_order.shuffle(rng);
 
-    for &idx in indices.iter().take(initial_reveals) {
-        puzzle_grid[idx] = full_grid[idx];
+    let mut revealed = 0;
+    for orbit in reveal_order {
+        if revealed >= initial_reveals.max(min_hints) {
+            break;
+        }
+        for &idx in orbit {
+            puzzle_grid[idx] = full_grid[idx];
+        }
+        revealed += orbit.len();
     }
 
     // 6. 유일 해가 될 때까지 추가 공개
+    let mut stats;
     loop {
         let hint_count = puzzle_grid.iter().filter(|&&x| x != 0).count();
 
         // 최대 힌트 수 초과 시 이 퍼즐 포기
         if hint_count > max_hints {
-            return None;
+            return Attempt::Rejected(None);
         }
 
-        let count = solver::count_solutions(&puzzle_grid, &constraints, 2);
+        let (count, last_stats) = match meter.count_solutions(geometry, &puzzle_grid, &constraints) {
+            Ok(found) => found,
+            Err(err) => return Attempt::Stopped(err),
+        };
+        stats = last_stats;
 
         if count == 1 {
-            // 유일 해 발견 - 힌트 수가 범위 내인지 확인
-            if hint_count >= min_hints {
-                break;
-            }
-            // 최소 힌트 미만이면 수락 (더 어려운 퍼즐은 허용)
+            // 유일 해 발견 (힌트 수는 처음부터 최소 이상)
             break;
         }
 
         if count == 0 {
             // 해가 없음 - 버그 또는 잘못된 제약
-            return None;
+            return Attempt::Rejected(None);
         }
 
         // 다중 해: 추가 셀 공개
-        let unrevealed: Vec<usize> = (0..81).filter(|&i| puzzle_grid[i] == 0).collect();
+        let unrevealed: Vec<&Vec<usize>> = orbits.iter().filter(|orbit| puzzle_grid[orbit[0]] == 0).collect();
         if unrevealed.is_empty() {
             break;
         }
 
-        let &pick = unrevealed.choose(rng).unwrap();
-        puzzle_grid[pick] = full_grid[pick];
+        for &idx in *unrevealed.choose(rng).unwrap() {
+            puzzle_grid[idx] = full_grid[idx];
+        }
+    }
+
+    // 7. 최소화: 남은 힌트가 모두 필요해질 때까지 제거
+    if minimize {
+        if let Err(err) = minimize_clues(geometry, &mut puzzle_grid, &mut constraints, &orbits, &inequality_orbits, &mut stats, meter, rng) {
+            return Attempt::Stopped(err);
+        }
     }
 
-    // 7. 최종 힌트 수 검증
+    // 8. 최종 힌트 수 검증
     let final_hint_count = puzzle_grid.iter().filter(|&&x| x != 0).count();
-    if final_hint_count < min_hints || final_hint_count > max_hints {
-        return None;
+    if (final_hint_count < min_hints && !minimize) || final_hint_count > max_hints {
+        return Attempt::Rejected(None);
     }
 
     // Convert grid to i8 for export
-    let mut export_grid = [-1i8; 81];
-    for i in 0..81 {
-        if puzzle_grid[i] != 0 {
-            export_grid[i] = puzzle_grid[i] as i8;
+    let export_grid: Vec<i8> = puzzle_grid.iter()
+        .map(|&v| if v != 0 { v as i8 } else { -1 })
+        .collect();
+
+    let mut puzzle = Puzzle {
+        geometry: geometry.clone(),
+        grid: export_grid,
+        all_dots: options.dots && options.all_dots,
+        ..Puzzle::default()
+    };
+    for constraint in constraints {
+        match constraint {
+            Constraint::Inequality(iq) => puzzle.constraints.push(iq),
+            Constraint::Cage(cage) => puzzle.cages.push(cage),
+            Constraint::Dot(dot) => puzzle.dots.push(dot),
+            Constraint::Thermo(thermo) => puzzle.thermos.push(thermo),
+            // `all_dots`가 다시 만들어 낸다
+            Constraint::NoDot { .. } => {}
+        }
+    }
+
+    // 9. 논리 풀이 평가가 목표 범위 밖이면 버림
+    let candidate = Candidate { puzzle, stats, fallback: false };
+    if let Some(band) = band {
+        let rating = rating::rate(&candidate.puzzle);
+        if !band.contains(&rating) {
+            return Attempt::Rejected(Some((candidate, rating)));
         }
     }
 
-    Some(Puzzle {
-        grid: export_grid.to_vec(),
-        constraints,
-    })
+    Attempt::Accepted(candidate)
+}
+
+/// 공개된 숫자 힌트 궤도와 남긴 블록 간 부등호 궤도를 무작위 순서로 하나씩
+/// 빼 보고 유일 해가 유지되면 그대로 뺀다. 힌트를 빼면 해가 늘어나기만 하므로,
+/// 한 번 빼지 못한 궤도는 끝까지 필요하고 결과는 더 줄일 수 없다.
+/// `stats`는 마지막으로 유일성을 확인한 탐색으로 바뀐다. 힌트마다 예산과 취소를 확인한다.
+#[allow(clippy::too_many_arguments)]
+fn minimize_clues(
+    geometry: &Geometry,
+    grid: &mut sudoku::Grid,
+    constraints: &mut Vec<Constraint>,
+    digit_orbits: &[Vec<usize>],
+    inequality_orbits: &[Vec<Inequality>],
+    stats: &mut SolverStats,
+    meter: &mut Meter,
+    rng: &mut impl Rng,
+) -> Result<(), GenerateError> {
+    enum Clue<'a> {
+        Digits(Vec<(usize, u8)>),
+        Inequalities(&'a [Inequality]),
+    }
+    let mut clues: Vec<Clue> = digit_orbits.iter()
+        .filter(|orbit| grid[orbit[0]] != 0)
+        .map(|orbit| Clue::Digits(orbit.iter().map(|&i| (i, grid[i])).collect()))
+        .chain(inequality_orbits.iter().map(|orbit| Clue::Inequalities(orbit)))
+        .collect();
+    clues.shuffle(rng);
+
+    for clue in &clues {
+        match clue {
+            Clue::Digits(digits) => digits.iter().for_each(|&(idx, _)| grid[idx] = 0),
+            Clue::Inequalities(orbit) => constraints.retain(|c| !matches!(c, Constraint::Inequality(iq) if orbit.contains(iq))),
+        }
+        meter.check()?;
+        let (count, last_stats) = meter.count_solutions(geometry, grid, constraints)?;
+        if count == 1 {
+            *stats = last_stats;
+            continue;
+        }
+        // 이 궤도는 필요하므로 되돌린다
+        match clue {
+            Clue::Digits(digits) => digits.iter().for_each(|&(idx, value)| grid[idx] = value),
+            Clue::Inequalities(orbit) => constraints.extend(orbit.iter().map(|&iq| Constraint::from(iq))),
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -186,7 +828,7 @@ mod tests {
     fn test_easy_hint_range() {
         let (min, max) = Difficulty::Easy.hint_range();
         for _ in 0..5 {
-            let puzzle = generate("Easy");
+            let puzzle = generate("Easy").unwrap();
             let hints = count_hints(&puzzle);
             assert!(hints >= min && hints <= max,
                 "Easy: hints={} not in range [{}, {}]", hints, min, max);
@@ -197,7 +839,7 @@ mod tests {
     fn test_normal_hint_range() {
         let (min, max) = Difficulty::Normal.hint_range();
         for _ in 0..5 {
-            let puzzle = generate("Normal");
+            let puzzle = generate("Normal").unwrap();
             let hints = count_hints(&puzzle);
             assert!(hints >= min && hints <= max,
                 "Normal: hints={} not in range [{}, {}]", hints, min, max);
@@ -208,7 +850,7 @@ mod tests {
     fn test_hard_hint_range() {
         let (min, max) = Difficulty::Hard.hint_range();
         for _ in 0..5 {
-            let puzzle = generate("Hard");
+            let puzzle = generate("Hard").unwrap();
             let hints = count_hints(&puzzle);
             assert!(hints >= min && hints <= max,
                 "Hard: hints={} not in range [{}, {}]", hints, min, max);
@@ -219,7 +861,7 @@ mod tests {
     fn test_expert_hint_range() {
         let (min, max) = Difficulty::Expert.hint_range();
         for _ in 0..5 {
-            let puzzle = generate("Expert");
+            let puzzle = generate("Expert").unwrap();
             let hints = count_hints(&puzzle);
             assert!(hints >= min && hints <= max,
                 "Expert: hints={} not in range [{}, {}]", hints, min, max);
@@ -243,13 +885,13 @@ mod tests {
     fn test_classic_no_hints() {
         // Classic: 숫자 힌트 0개, 최소한의 블록 간 부등호
         for _ in 0..3 {
-            let puzzle = generate("Classic");
+            let puzzle = generate("Classic").unwrap();
             let hints = count_hints(&puzzle);
             assert_eq!(hints, 0, "Classic should have 0 hints, got {}", hints);
 
             // 블록 간 부등호 비율 확인 (8% 이하)
             let inter_count = puzzle.constraints.iter()
-                .filter(|iq| !iq.is_intra_block())
+                .filter(|iq| !iq.is_intra_block(&puzzle.geometry))
                 .count();
             let total_inter = 72;  // 최대 블록 간 부등호 수
             let ratio = inter_count as f64 / total_inter as f64;
@@ -257,16 +899,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_seeded_is_reproducible() {
+        for difficulty in ["Easy", "Hard"] {
+            let a = generate_seeded(difficulty, 42).unwrap();
+            let b = generate_seeded(difficulty, 42).unwrap();
+            assert_eq!(a, b, "{}: same seed produced different puzzles", difficulty);
+        }
+
+        let a = generate_seeded("Normal", 1).unwrap();
+        let b = generate_seeded("Normal", 2).unwrap();
+        assert_ne!(a.grid, b.grid, "different seeds should give different puzzles");
+    }
+
+    #[test]
+    fn test_rating_within_band() {
+        for difficulty_str in ["Easy", "Normal", "Hard", "Expert"] {
+            let band = Difficulty::from_str(difficulty_str).unwrap().rating_band();
+            for seed in 0..3 {
+                let rating = rating::rate(&generate_seeded(difficulty_str, seed).unwrap());
+                assert!(band.contains(&rating),
+                    "{}: rating {:?} outside {:?}", difficulty_str, rating, band);
+            }
+        }
+    }
+
     #[test]
     fn test_hint_distribution() {
         // 각 난이도별 20개 샘플 생성하여 분포 확인
         for (name, difficulty_str) in [("Easy", "Easy"), ("Normal", "Normal"), ("Hard", "Hard"), ("Expert", "Expert")] {
-            let (min, max) = Difficulty::from_str(difficulty_str).hint_range();
+            let (min, max) = Difficulty::from_str(difficulty_str).unwrap().hint_range();
             let mut all_hints: Vec<usize> = Vec::new();
             let mut out_of_range = 0;
 
             for _ in 0..20 {
-                let puzzle = generate(difficulty_str);
+                let puzzle = generate(difficulty_str).unwrap();
                 let hints = count_hints(&puzzle);
                 all_hints.push(hints);
                 if hints < min || hints > max {
@@ -286,4 +953,333 @@ mod tests {
                 name, out_of_range, min, max, min_h, max_h);
         }
     }
+
+    #[test]
+    fn test_latin_unique_without_boxes() {
+        for difficulty_str in ["LatinEasy", "LatinNormal", "LatinHard"] {
+            for size in 4..=6 {
+                let puzzle = generate_with_rng(difficulty_str, Some(size), &mut ChaCha8Rng::seed_from_u64(size as u64)).unwrap();
+                assert_eq!(puzzle.geometry, Geometry::latin(size));
+                assert_eq!(puzzle.grid.len(), size * size);
+                assert!(puzzle.constraints.iter().all(|iq| !iq.is_intra_block(&puzzle.geometry)));
+                assert_eq!(solver::count_solutions(&puzzle.geometry, &puzzle.givens(), &puzzle.rules(), 2), 1,
+                    "{} {}x{}: solution is not unique", difficulty_str, size, size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_latin_hint_range() {
+        for difficulty_str in ["LatinEasy", "LatinNormal", "LatinHard"] {
+            let difficulty = Difficulty::from_str(difficulty_str).unwrap();
+            for size in [8, 9] {
+                let ((min, max), _) = difficulty.clue_presets(&Geometry::latin(size));
+                let options = GenerateOptions { size: Some(size), ..GenerateOptions::default() };
+                for seed in 0..3 {
+                    let candidate = generate_with_budget(difficulty_str, &options, &mut ChaCha8Rng::seed_from_u64(seed), Budget::default(), |_| true).unwrap();
+                    let hints = count_hints(&candidate.puzzle);
+                    assert!(!candidate.fallback && hints >= min && hints <= max,
+                        "{} {}x{} seed {}: hints={} not in range [{}, {}]", difficulty_str, size, size, seed, hints, min, max);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported_sizes_are_rejected() {
+        assert_eq!(Difficulty::LatinEasy.geometry(None), Ok(Geometry::latin(LATIN_DEFAULT_SIZE)));
+        assert_eq!(Difficulty::LatinEasy.geometry(Some(5)), Ok(Geometry::latin(5)));
+        assert_eq!(Difficulty::LatinEasy.geometry(Some(2)), Err(GenerateError::UnsupportedSize(2)));
+        assert_eq!(Difficulty::LatinEasy.geometry(Some(12)), Err(GenerateError::UnsupportedSize(12)));
+        assert_eq!(Difficulty::Easy.geometry(None), Ok(Geometry::classic()));
+        assert_eq!(Difficulty::Easy.geometry(Some(5)), Err(GenerateError::UnsupportedSize(5)));
+        assert_eq!(Difficulty::Easy.geometry(Some(8)), Err(GenerateError::UnsupportedSize(8)));
+        assert_eq!(Difficulty::Classic.geometry(Some(8)), Err(GenerateError::UnsupportedSize(8)));
+        assert_eq!(Difficulty::Classic.geometry(Some(12)), Err(GenerateError::UnsupportedSize(12)));
+        assert_eq!(generate_with_rng("Hard", Some(7), &mut ChaCha8Rng::seed_from_u64(1)),
+            Err(GenerateError::UnsupportedSize(7)));
+    }
+
+    #[test]
+    fn test_boxed_sizes() {
+        for (size, difficulties) in [
+            (4, &["Easy", "Normal", "Hard", "Expert", "Classic"][..]),
+            (6, &["Easy", "Normal", "Hard", "Expert", "Classic"][..]),
+            (12, &["Easy"][..]),
+        ] {
+            let geometry = Geometry::boxed(size).unwrap();
+            for &difficulty_str in difficulties {
+                let difficulty = Difficulty::from_str(difficulty_str).unwrap();
+                let ((min, max), _) = difficulty.clue_presets(&geometry);
+                let puzzle = generate_with_rng(difficulty_str, Some(size), &mut ChaCha8Rng::seed_from_u64(7)).unwrap();
+                assert_eq!(puzzle.geometry, geometry);
+                assert_eq!(puzzle.grid.len(), size * size);
+                let hints = count_hints(&puzzle);
+                assert!(hints >= min && hints <= max,
+                    "{} {}x{}: hints={} not in range [{}, {}]", difficulty_str, size, size, hints, min, max);
+                assert_eq!(solver::count_solutions(&geometry, &puzzle.givens(), &puzzle.rules(), 2), 1,
+                    "{} {}x{}: solution is not unique", difficulty_str, size, size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fallback_is_marked() {
+        // 범위 안의 퍼즐만 fallback이 아니다
+        let geometry = Geometry::boxed(4).unwrap();
+        let ((min, max), _) = Difficulty::Expert.clue_presets(&geometry);
+        let band = scale_band(&geometry, Difficulty::Expert.rating_band());
+        let options = GenerateOptions { size: Some(4), ..GenerateOptions::default() };
+        for seed in 0..20 {
+            let candidate = generate_with_budget("Expert", &options, &mut ChaCha8Rng::seed_from_u64(seed), Budget::default(), |_| true).unwrap();
+            let hints = count_hints(&candidate.puzzle);
+            let in_range = hints >= min && hints <= max && band.contains(&rating::rate(&candidate.puzzle));
+            assert!(candidate.fallback || in_range, "seed {}: hints={} outside [{}, {}] or band", seed, hints, min, max);
+        }
+        let candidate = generate_with_budget("Hard", &GenerateOptions::default(), &mut ChaCha8Rng::seed_from_u64(1), Budget::default(), |_| true).unwrap();
+        assert!(!candidate.fallback);
+    }
+
+    #[test]
+    fn test_small_boards_reach_every_difficulty() {
+        let geometry = Geometry::boxed(4).unwrap();
+        let options = GenerateOptions { size: Some(4), ..GenerateOptions::default() };
+        for difficulty_str in ["Easy", "Normal", "Hard", "Expert", "Classic"] {
+            let ((min, max), _) = Difficulty::from_str(difficulty_str).unwrap().clue_presets(&geometry);
+            for seed in 0..20 {
+                let candidate = generate_with_budget(difficulty_str, &options, &mut ChaCha8Rng::seed_from_u64(seed), Budget::default(), |_| true).unwrap();
+                let hints = count_hints(&candidate.puzzle);
+                assert!(!candidate.fallback && hints >= min && hints <= max,
+                    "{} 4x4 seed {}: hints={} not in range [{}, {}]", difficulty_str, seed, hints, min, max);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_difficulty_is_an_error() {
+        assert_eq!(generate("Impossible"), Err(GenerateError::InvalidDifficulty("Impossible".to_string())));
+        assert!("hard".parse::<Difficulty>().is_err());
+        assert!(matches!("LatinHard".parse::<Difficulty>(), Ok(Difficulty::LatinHard)));
+    }
+
+    #[test]
+    fn test_budget_and_cancellation() {
+        // 예산이 충분하면 예산 없는 생성과 같은 퍼즐
+        let mut attempts = Vec::new();
+        let budgeted = generate_with_budget("Hard", &GenerateOptions::default(), &mut ChaCha8Rng::seed_from_u64(11), Budget::default(), |progress| {
+            attempts.push(progress.attempt);
+            true
+        }).unwrap();
+        assert_eq!(budgeted.puzzle, generate_seeded("Hard", 11).unwrap());
+        let (count, stats) = solver::count_solutions_with_stats(&budgeted.puzzle.geometry, &budgeted.puzzle.givens(), &budgeted.puzzle.rules(), 2);
+        assert_eq!((count, stats), (1, budgeted.stats));
+        assert_eq!(attempts, (1..=attempts.len()).collect::<Vec<_>>());
+
+        let tiny = Budget { max_nodes: Some(0), ..Budget::default() };
+        assert_eq!(generate_with_budget("Classic", &GenerateOptions::default(), &mut ChaCha8Rng::seed_from_u64(1), tiny, |_| true),
+            Err(GenerateError::BudgetExceeded));
+        let expired = Budget { max_millis: Some(0.0), ..Budget::default() };
+        assert_eq!(generate_with_budget("Easy", &GenerateOptions::default(), &mut ChaCha8Rng::seed_from_u64(1), expired, |_| true),
+            Err(GenerateError::BudgetExceeded));
+        // 직소 배치도 예산을 확인하므로 첫 시도까지 가지 않는다
+        let jigsaw = GenerateOptions { jigsaw: true, ..GenerateOptions::default() };
+        let mut calls = 0;
+        assert_eq!(generate_with_budget("Easy", &jigsaw, &mut ChaCha8Rng::seed_from_u64(1), expired, |_| {
+            calls += 1;
+            true
+        }), Err(GenerateError::BudgetExceeded));
+        assert_eq!(calls, 0);
+
+        // 첫 시도에서 실패하는 시드를 찾아 첫 콜백에서 취소
+        let seed = (0..).find(|&seed| {
+            let mut calls = 0;
+            generate_with_budget("Expert", &GenerateOptions::default(), &mut ChaCha8Rng::seed_from_u64(seed), Budget::default(), |_| {
+                calls += 1;
+                false
+            }).is_err() && calls == 1
+        }).unwrap();
+        let mut best = None;
+        let result = generate_with_budget("Expert", &GenerateOptions::default(), &mut ChaCha8Rng::seed_from_u64(seed), Budget::default(), |progress| {
+            best = progress.best.cloned();
+            false
+        });
+        assert_eq!(result, Err(GenerateError::Cancelled));
+        if let Some(Candidate { puzzle: best, .. }) = best {
+            assert_eq!(solver::count_solutions(&best.geometry, &best.givens(), &best.rules(), 2), 1);
+        }
+    }
+
+    #[test]
+    fn test_minimized_puzzles_are_irreducible() {
+        let minimize = GenerateOptions { minimize: true, ..GenerateOptions::default() };
+        let cases = [
+            ("Classic", minimize.clone()),
+            ("Hard", GenerateOptions { size: Some(6), ..minimize.clone() }),
+            ("LatinNormal", GenerateOptions { size: Some(5), ..minimize }),
+        ];
+        for (difficulty_str, options) in cases {
+            let candidate = generate_with_budget(difficulty_str, &options, &mut ChaCha8Rng::seed_from_u64(3), Budget::default(), |_| true).unwrap();
+            let puzzle = candidate.puzzle;
+            let givens = puzzle.givens();
+            let unique = |grid: &sudoku::Grid, constraints: &[Inequality]| {
+                let rules: Vec<Constraint> = constraints.iter().map(|&iq| iq.into()).collect();
+                solver::count_solutions(&puzzle.geometry, grid, &rules, 2) == 1
+            };
+            assert!(unique(&givens, &puzzle.constraints));
+
+            for idx in (0..givens.len()).filter(|&i| givens[i] != 0) {
+                let mut grid = givens.clone();
+                grid[idx] = 0;
+                assert!(!unique(&grid, &puzzle.constraints), "{}: clue in cell {} is redundant", difficulty_str, idx);
+            }
+            for (k, iq) in puzzle.constraints.iter().enumerate().filter(|(_, iq)| !iq.is_intra_block(&puzzle.geometry)) {
+                let mut constraints = puzzle.constraints.clone();
+                constraints.remove(k);
+                assert!(!unique(&givens, &constraints), "{}: {:?} is redundant", difficulty_str, iq);
+            }
+        }
+        // 최소화는 명시적으로 켤 때만 한다
+        assert!(!GenerateOptions::default().minimize);
+    }
+
+    #[test]
+    fn test_symmetric_clues() {
+        for (difficulty_str, size, symmetry) in [
+            ("Normal", 9, Symmetry::Rotational),
+            ("Hard", 9, Symmetry::Vertical),
+            ("Easy", 6, Symmetry::Horizontal),
+            ("LatinNormal", 6, Symmetry::Diagonal),
+        ] {
+            let options = GenerateOptions { size: Some(size), symmetry, symmetric_inequalities: true, ..GenerateOptions::default() };
+            let puzzle = generate_with_budget(difficulty_str, &options, &mut ChaCha8Rng::seed_from_u64(5), Budget::default(), |_| true)
+                .unwrap().puzzle;
+            let geometry = &puzzle.geometry;
+            assert_eq!(solver::count_solutions(geometry, &puzzle.givens(), &puzzle.rules(), 2), 1);
+
+            for idx in 0..geometry.cells() {
+                let image = symmetry.image(geometry, idx);
+                assert_eq!(symmetry.image(geometry, image), idx);
+                assert_eq!(puzzle.grid[idx] > 0, puzzle.grid[image] > 0, "{:?}: cell {} breaks symmetry", symmetry, idx);
+            }
+            let has_pair = |a: usize, b: usize| puzzle.constraints.iter().any(|iq| (iq.a, iq.b) == (a, b) || (iq.a, iq.b) == (b, a));
+            for iq in &puzzle.constraints {
+                assert!(has_pair(symmetry.image(geometry, iq.a), symmetry.image(geometry, iq.b)),
+                    "{:?}: {:?} has no mirror image", symmetry, iq);
+            }
+        }
+        assert_eq!(Symmetry::Rotational.orbits(&Geometry::latin(3)).len(), 5);
+        assert_eq!("Diagonal".parse(), Ok(Symmetry::Diagonal));
+    }
+
+    /// 예산 없이 옵션대로 만든 퍼즐
+    fn generate_with_options(difficulty_str: &str, options: &GenerateOptions, seed: u64) -> Result<Puzzle, GenerateError> {
+        generate_with_budget(difficulty_str, options, &mut ChaCha8Rng::seed_from_u64(seed), Budget::default(), |_| true)
+            .map(|candidate| candidate.puzzle)
+    }
+
+    /// 옵션대로 만든 퍼즐이 유일 해를 갖는지 확인하고 퍼즐과 그 해를 돌려준다
+    fn generate_unique(difficulty_str: &str, options: &GenerateOptions, seed: u64) -> (Puzzle, sudoku::Grid) {
+        let puzzle = generate_with_options(difficulty_str, options, seed).unwrap();
+        let (geometry, givens, rules) = (&puzzle.geometry, puzzle.givens(), puzzle.rules());
+        assert_eq!(solver::count_solutions(geometry, &givens, &rules, 2), 1, "{}: solution is not unique", difficulty_str);
+        let solution = solver::solve(geometry, &givens, &rules).unwrap();
+        (puzzle, solution)
+    }
+
+    #[test]
+    fn test_killer_cages() {
+        for (difficulty_str, size) in [("Normal", 6), ("LatinHard", 6)] {
+            let options = GenerateOptions { size: Some(size), max_cage_size: Some(4), ..GenerateOptions::default() };
+            let (puzzle, solution) = generate_unique(difficulty_str, &options, 8);
+            let geometry = &puzzle.geometry;
+            let mut caged = vec![0; geometry.cells()];
+            for cage in &puzzle.cages {
+                assert!(cage.cells.len() <= 4);
+                assert_eq!(cage.cells.iter().map(|&idx| solution[idx] as u32).sum::<u32>(), cage.sum);
+                cage.cells.iter().for_each(|&idx| caged[idx] += 1);
+            }
+            assert!(caged.iter().all(|&count| count == 1));
+        }
+    }
+
+    #[test]
+    fn test_kropki_dots() {
+        for (difficulty_str, all_dots) in [("Hard", false), ("LatinHard", true)] {
+            let options = GenerateOptions { size: Some(6), dots: true, all_dots, ..GenerateOptions::default() };
+            let (puzzle, solution) = generate_unique(difficulty_str, &options, 4);
+            let geometry = &puzzle.geometry;
+            assert_eq!(puzzle.all_dots, all_dots);
+            assert!(!puzzle.dots.is_empty());
+            assert_eq!(futoshiki::generate_dots_from_grid(geometry, &solution), puzzle.dots);
+        }
+    }
+
+    #[test]
+    fn test_thermometers() {
+        for difficulty_str in ["Hard", "LatinNormal"] {
+            let options = GenerateOptions { size: Some(6), thermometers: 4, ..GenerateOptions::default() };
+            let (puzzle, solution) = generate_unique(difficulty_str, &options, 2);
+            assert!(!puzzle.thermos.is_empty() && puzzle.thermos.len() <= 4);
+            for thermo in &puzzle.thermos {
+                assert!(thermo.cells.windows(2).all(|pair| solution[pair[0]] < solution[pair[1]]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_extra_regions() {
+        let cases = [
+            ("Normal", 9, vec![RegionSet::Diagonals]),
+            ("Hard", 9, vec![RegionSet::Windoku]),
+            ("LatinNormal", 6, vec![RegionSet::Diagonals]),
+        ];
+        for (difficulty_str, size, regions) in cases {
+            let options = GenerateOptions { size: Some(size), regions, ..GenerateOptions::default() };
+            let (puzzle, solution) = generate_unique(difficulty_str, &options, 1);
+            let geometry = &puzzle.geometry;
+            assert!(!geometry.regions.is_empty());
+            for region in &geometry.regions {
+                let mut values: Vec<u8> = region.iter().map(|&idx| solution[idx]).collect();
+                values.sort_unstable();
+                assert_eq!(values, (1..=size as u8).collect::<Vec<_>>());
+            }
+        }
+
+        let windoku = GenerateOptions { regions: vec![RegionSet::Windoku], ..GenerateOptions::default() };
+        assert_eq!(generate_with_options("LatinEasy", &windoku, 1).err(),
+            Some(GenerateError::InvalidRegions));
+
+        // 두 영역이 세 칸을 공유하면 남은 4, 5번 칸이 같아야 하지만 둘은 같은 행이다
+        let clash = RegionSet::Custom(vec![vec![0, 1, 2, 4], vec![0, 1, 2, 5]]);
+        for jigsaw in [false, true] {
+            let options = GenerateOptions { size: Some(4), regions: vec![clash.clone()], jigsaw, ..GenerateOptions::default() };
+            assert_eq!(generate_with_options("LatinEasy", &options, 1).err(),
+                Some(GenerateError::InvalidRegions));
+        }
+    }
+
+    #[test]
+    fn test_jigsaw_boards() {
+        for (difficulty_str, size) in [("Hard", 9), ("Classic", 6), ("LatinNormal", 5)] {
+            let options = GenerateOptions { size: Some(size), jigsaw: true, ..GenerateOptions::default() };
+            let (puzzle, solution) = generate_unique(difficulty_str, &options, 3);
+            let geometry = &puzzle.geometry;
+            assert!(geometry.jigsaw.is_some() && geometry.boxes.is_none());
+            // 일반 블록(블록 모양이 없는 크기는 행)에서 칸을 맞바꿔 불규칙한 배치가 되어야 한다
+            let regular = Geometry::boxed(size).unwrap_or_else(|| Geometry::latin(size));
+            let regular_map: Vec<usize> = (0..geometry.cells()).map(|i| regular.box_of(i).unwrap_or_else(|| regular.row(i))).collect();
+            assert_ne!(geometry.jigsaw.as_ref(), Some(&regular_map), "{}: jigsaw layout is regular", difficulty_str);
+            for b in 0..size {
+                let mut values: Vec<u8> = (0..geometry.cells()).filter(|&i| geometry.box_of(i) == Some(b)).map(|i| solution[i]).collect();
+                values.sort_unstable();
+                assert_eq!(values, (1..=size as u8).collect::<Vec<_>>(), "{}: box {} repeats a value", difficulty_str, b);
+            }
+            // Classic는 직소 블록 안의 부등호를 모두 남긴다
+            if difficulty_str == "Classic" {
+                let mut intra = futoshiki::generate_from_grid(geometry, &solution).into_iter().filter(|iq| iq.is_intra_block(geometry));
+                assert!(intra.all(|iq| puzzle.constraints.contains(&iq)));
+            }
+        }
+    }
 }
+
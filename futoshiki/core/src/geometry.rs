@@ -0,0 +1,350 @@
+//! Board shape: side length and the boxes inside it, if any.
+//!
+//! A Sudoku-Futoshiki board has rows, columns and boxes as all-different
+//! houses; a pure Futoshiki board is a Latin square with rows and columns only.
+//! Either can carry extra regions, such as the diagonals of X-Sudoku. On a
+//! jigsaw board the boxes are irregular regions given cell by cell.
+
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BoxShape {
+    pub rows: usize,
+    pub cols: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Geometry {
+    pub size: usize,
+    pub boxes: Option<BoxShape>,
+    /// Extra all-different regions of `size` cells each.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<Vec<usize>>,
+    /// Box of each cell on a jigsaw board, in place of `boxes`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jigsaw: Option<Vec<usize>>,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Geometry::classic()
+    }
+}
+
+/// One all-different unit of the board.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", content = "index")]
+pub enum House {
+    Row(usize),
+    Column(usize),
+    Box(usize),
+    /// Index into `Geometry::regions`.
+    Region(usize),
+}
+
+impl House {
+    pub fn cells(&self, geometry: &Geometry) -> Vec<usize> {
+        let n = geometry.size;
+        match *self {
+            House::Row(r) => (0..n).map(|k| r * n + k).collect(),
+            House::Column(c) => (0..n).map(|k| k * n + c).collect(),
+            House::Box(b) => (0..geometry.cells())
+                .filter(|&i| geometry.box_of(i) == Some(b))
+                .collect(),
+            House::Region(k) => geometry.regions[k].clone(),
+        }
+    }
+
+    pub fn contains(&self, geometry: &Geometry, idx: usize) -> bool {
+        match *self {
+            House::Row(r) => geometry.row(idx) == r,
+            House::Column(c) => geometry.col(idx) == c,
+            House::Box(b) => geometry.box_of(idx) == Some(b),
+            House::Region(k) => geometry.regions[k].contains(&idx),
+        }
+    }
+}
+
+/// A standard choice of extra regions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RegionSet {
+    /// Both main diagonals (X-Sudoku).
+    Diagonals,
+    /// Box-sized windows starting one cell in from the top left and one
+    /// cell apart: the four extra 3x3 windows of a 9x9 Windoku.
+    Windoku,
+    /// Regions given cell by cell.
+    Custom(Vec<Vec<usize>>),
+}
+
+impl RegionSet {
+    /// The regions on `geometry`. Windoku needs boxes, so `None` on a
+    /// Latin board.
+    pub fn regions(&self, geometry: &Geometry) -> Option<Vec<Vec<usize>>> {
+        let n = geometry.size;
+        match self {
+            RegionSet::Diagonals => Some(vec![
+                (0..n).map(|k| k * n + k).collect(),
+                (0..n).map(|k| k * n + (n - 1 - k)).collect(),
+            ]),
+            RegionSet::Windoku => {
+                let shape = geometry.boxes?;
+                let starts = |len: usize| (1..).step_by(len + 1).take_while(move |&start| start + len < n);
+                Some(starts(shape.rows)
+                    .flat_map(|r| starts(shape.cols).map(move |c| (r, c)))
+                    .map(|(r, c)| (0..geometry.cells())
+                        .filter(|&i| (r..r + shape.rows).contains(&geometry.row(i)) && (c..c + shape.cols).contains(&geometry.col(i)))
+                        .collect())
+                    .collect())
+            }
+            RegionSet::Custom(regions) => Some(regions.clone()),
+        }
+    }
+}
+
+impl FromStr for RegionSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Diagonals" => Ok(RegionSet::Diagonals),
+            "Windoku" => Ok(RegionSet::Windoku),
+            _ => Err(format!("unknown region set \"{}\"", s)),
+        }
+    }
+}
+
+/// Side lengths that have a standard box shape.
+pub const BOXED_SIZES: [usize; 4] = [4, 6, 9, 12];
+
+/// Largest side length any board may have.
+pub const MAX_SIZE: usize = 12;
+
+impl Geometry {
+    /// 9x9 with 3x3 boxes.
+    pub fn classic() -> Self {
+        Geometry { size: 9, boxes: Some(BoxShape { rows: 3, cols: 3 }), regions: Vec::new(), jigsaw: None }
+    }
+
+    /// Sudoku-style board with the usual boxes for `size`: 2x2 on 4x4,
+    /// 2x3 on 6x6, 3x3 on 9x9 and 3x4 on 12x12. `None` for other sizes.
+    pub fn boxed(size: usize) -> Option<Self> {
+        let (rows, cols) = match size {
+            4 => (2, 2),
+            6 => (2, 3),
+            9 => (3, 3),
+            12 => (3, 4),
+            _ => return None,
+        };
+        Some(Geometry { size, boxes: Some(BoxShape { rows, cols }), regions: Vec::new(), jigsaw: None })
+    }
+
+    /// N×N Latin square without boxes.
+    pub fn latin(size: usize) -> Self {
+        Geometry { size, boxes: None, regions: Vec::new(), jigsaw: None }
+    }
+
+    /// Jigsaw board whose boxes are given by `map`, the box number of each
+    /// cell in row-major order. `None` unless the board is square and every
+    /// box is `size` orthogonally connected cells.
+    pub fn jigsaw(map: Vec<usize>) -> Option<Self> {
+        let size = (1..=map.len()).find(|&n| n * n >= map.len())?;
+        if size * size != map.len() {
+            return None;
+        }
+        let mut geometry = Geometry::latin(size);
+        for b in 0..size {
+            let cells: Vec<usize> = (0..map.len()).filter(|&i| map[i] == b).collect();
+            if cells.len() != size || !geometry.is_connected(&cells) {
+                return None;
+            }
+        }
+        geometry.jigsaw = Some(map);
+        Some(geometry)
+    }
+
+    /// Adds `regions` as extra houses. `None` unless each holds `size`
+    /// distinct cells of the board. Regions need not be connected (the
+    /// diagonals are not), and overlapping ones can still contradict each
+    /// other; `sudoku::is_fillable` tells whether any grid fits.
+    pub fn with_regions(mut self, regions: Vec<Vec<usize>>) -> Option<Self> {
+        for region in &regions {
+            let mut cells = region.clone();
+            cells.sort_unstable();
+            cells.dedup();
+            if cells.len() != self.size || region.len() != self.size || cells.iter().any(|&i| i >= self.cells()) {
+                return None;
+            }
+        }
+        self.regions.extend(regions);
+        Some(self)
+    }
+
+    /// Whether this describes a board the crate can work with: a side of
+    /// at most `MAX_SIZE`, boxes that tile it, a well-formed jigsaw map and
+    /// extra regions as `with_regions` accepts them. Generated geometries
+    /// always are; deserialised ones need checking.
+    pub fn is_valid(&self) -> bool {
+        if !(1..=MAX_SIZE).contains(&self.size) {
+            return false;
+        }
+        if self.boxes.is_some_and(|shape| shape.rows * shape.cols != self.size) {
+            return false;
+        }
+        if let Some(map) = &self.jigsaw {
+            if Geometry::jigsaw(map.clone()).map(|geometry| geometry.size) != Some(self.size) {
+                return false;
+            }
+        }
+        Geometry::latin(self.size).with_regions(self.regions.clone()).is_some()
+    }
+
+    pub fn cells(&self) -> usize {
+        self.size * self.size
+    }
+
+    /// Candidate mask with every value 1..=size set.
+    pub fn all_values(&self) -> u16 {
+        (1 << self.size) - 1
+    }
+
+    pub fn row(&self, idx: usize) -> usize {
+        idx / self.size
+    }
+
+    pub fn col(&self, idx: usize) -> usize {
+        idx % self.size
+    }
+
+    /// Whether the board has boxes, regular or jigsaw.
+    pub fn has_boxes(&self) -> bool {
+        self.boxes.is_some() || self.jigsaw.is_some()
+    }
+
+    pub fn box_of(&self, idx: usize) -> Option<usize> {
+        if let Some(map) = &self.jigsaw {
+            return Some(map[idx]);
+        }
+        let shape = self.boxes?;
+        let boxes_per_row = self.size / shape.cols;
+        Some((self.row(idx) / shape.rows) * boxes_per_row + self.col(idx) / shape.cols)
+    }
+
+    pub fn houses(&self) -> Vec<House> {
+        let n = self.size;
+        let mut houses: Vec<House> = (0..n).map(House::Row).collect();
+        houses.extend((0..n).map(House::Column));
+        if self.has_boxes() {
+            houses.extend((0..n).map(House::Box));
+        }
+        houses.extend((0..self.regions.len()).map(House::Region));
+        houses
+    }
+
+    /// The houses containing `idx`.
+    pub fn houses_of(&self, idx: usize) -> Vec<House> {
+        let mut houses = vec![House::Row(self.row(idx)), House::Column(self.col(idx))];
+        houses.extend(self.box_of(idx).map(House::Box));
+        houses.extend((0..self.regions.len()).filter(|&k| self.regions[k].contains(&idx)).map(House::Region));
+        houses
+    }
+
+    /// Whether `cells` form one orthogonally connected piece.
+    pub fn is_connected(&self, cells: &[usize]) -> bool {
+        let Some(&first) = cells.first() else {
+            return true;
+        };
+        let mut reached = vec![first];
+        let mut k = 0;
+        while k < reached.len() {
+            let idx = reached[k];
+            k += 1;
+            for &j in cells {
+                let adjacent = self.row(idx).abs_diff(self.row(j)) + self.col(idx).abs_diff(self.col(j)) == 1;
+                if adjacent && !reached.contains(&j) {
+                    reached.push(j);
+                }
+            }
+        }
+        reached.len() == cells.len()
+    }
+
+    /// Cells sharing a house with `idx`, excluding `idx` itself.
+    pub fn peers(&self, idx: usize) -> Vec<usize> {
+        let houses = self.houses_of(idx);
+        (0..self.cells())
+            .filter(|&j| j != idx && houses.iter().any(|h| h.contains(self, j)))
+            .collect()
+    }
+
+    /// Orthogonal neighbour pairs `(a, b)` with `a` left of or above `b`:
+    /// every horizontal pair row by row, then every vertical pair.
+    pub(crate) fn neighbour_pairs(&self) -> impl Iterator<Item = (usize, usize)> {
+        let n = self.size;
+        let horizontal = (0..n).flat_map(move |row| (0..n - 1).map(move |col| (row * n + col, row * n + col + 1)));
+        let vertical = (0..n - 1).flat_map(move |row| (0..n).map(move |col| (row * n + col, (row + 1) * n + col)));
+        horizontal.chain(vertical)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boxes_tile_the_board() {
+        for size in BOXED_SIZES {
+            let geometry = Geometry::boxed(size).unwrap();
+            for b in 0..size {
+                let cells = House::Box(b).cells(&geometry);
+                assert_eq!(cells.len(), size, "{}x{}: box {} has {} cells", size, size, b, cells.len());
+            }
+            // Row, column and box peers, counted once each.
+            let shape = geometry.boxes.unwrap();
+            assert_eq!(geometry.peers(0).len(), 2 * (size - 1) + (shape.rows - 1) * (shape.cols - 1));
+        }
+        assert_eq!(Geometry::boxed(9), Some(Geometry::classic()));
+        assert_eq!(Geometry::boxed(8), None);
+    }
+
+    #[test]
+    fn test_extra_regions() {
+        let classic = Geometry::classic();
+        let windows = RegionSet::Windoku.regions(&classic).unwrap();
+        assert_eq!(windows.len(), 4);
+        assert_eq!(windows[0], vec![10, 11, 12, 19, 20, 21, 28, 29, 30]);
+        assert_eq!(windows[3], vec![50, 51, 52, 59, 60, 61, 68, 69, 70]);
+        assert_eq!(RegionSet::Windoku.regions(&Geometry::latin(9)), None);
+
+        let diagonals = RegionSet::Diagonals.regions(&classic).unwrap();
+        let geometry = classic.with_regions(diagonals).unwrap();
+        // The centre lies on both diagonals.
+        assert_eq!(geometry.houses_of(40), vec![House::Row(4), House::Column(4), House::Box(4), House::Region(0), House::Region(1)]);
+        assert_eq!(geometry.peers(0).len(), 20 + 6);
+        assert_eq!(House::Region(1).cells(&geometry)[0], 8);
+
+        assert_eq!(Geometry::latin(4).with_regions(vec![vec![0, 1, 2]]), None);
+        assert_eq!(Geometry::latin(4).with_regions(vec![vec![0, 1, 1, 2]]), None);
+        assert_eq!("Windoku".parse(), Ok(RegionSet::Windoku));
+    }
+
+    #[test]
+    fn test_jigsaw_boxes() {
+        let map = vec![
+            0, 0, 0, 1,
+            2, 0, 1, 1,
+            2, 3, 3, 1,
+            2, 2, 3, 3,
+        ];
+        let geometry = Geometry::jigsaw(map).unwrap();
+        assert_eq!(geometry.size, 4);
+        assert_eq!(House::Box(0).cells(&geometry), vec![0, 1, 2, 5]);
+        assert_eq!(geometry.houses().len(), 12);
+        assert_eq!(geometry.peers(5), vec![0, 1, 2, 4, 6, 7, 9, 13]);
+
+        // Scattered boxes, and a box of the wrong size.
+        assert_eq!(Geometry::jigsaw(vec![0, 1, 0, 1, 2, 2, 3, 3, 0, 1, 2, 2, 1, 0, 3, 3]), None);
+        assert_eq!(Geometry::jigsaw(vec![0, 0, 0, 0, 0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 1]), None);
+    }
+}
@@ -0,0 +1,175 @@
+//! Next-step hints for a board in progress.
+//!
+//! The player's entries and pencil marks are checked against the solution
+//! first; only a clean board gets the next logical deduction.
+
+use serde::Serialize;
+use crate::futoshiki::{Inequality, Puzzle};
+use crate::logic::{Candidate, LogicState, Technique};
+use crate::solver;
+use crate::sudoku::Grid;
+use crate::geometry::House;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MistakeKind {
+    /// The entered value is not the solution value.
+    Entry,
+    /// The pencil marks no longer contain the solution value.
+    PencilMark,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Hint {
+    Mistake {
+        cell: usize,
+        mistake: MistakeKind,
+    },
+    Deduction {
+        /// Cell the deduction places a value in or first removes one from.
+        cell: usize,
+        value: Option<u8>,
+        eliminations: Vec<Candidate>,
+        technique: Technique,
+        technique_name: &'static str,
+        house: Option<House>,
+        /// Inequalities (thermometer segments included) and peer cells
+        /// that justify the deduction.
+        constraints: Vec<Inequality>,
+        peers: Vec<usize>,
+    },
+    /// No technique applies any more; the solution value of the most
+    /// constrained empty cell is given instead.
+    Reveal {
+        cell: usize,
+        value: u8,
+    },
+    Solved,
+    /// The givens clash with each other or with the rules, so no grid
+    /// completes them and no step can be trusted.
+    NoSolution,
+}
+
+/// `entries` and `pencil_marks` hold one value per cell. An entry of 0 or
+/// -1 is empty; a mark mask of 0 means the player has not marked the cell.
+pub fn next_hint(puzzle: &Puzzle, entries: &[i8], pencil_marks: &[u16]) -> Hint {
+    let geometry = &puzzle.geometry;
+    let cells = geometry.cells();
+    let board = puzzle.board(entries);
+
+    let Some(solution) = solver::solve(geometry, &puzzle.givens(), &puzzle.rules()) else {
+        return Hint::NoSolution;
+    };
+    if let Some(hint) = find_mistake(&board, pencil_marks, &solution) {
+        return hint;
+    }
+
+    let mut state = LogicState::new(geometry, &board);
+    for (i, &marks) in pencil_marks.iter().enumerate().take(cells) {
+        if board[i] == 0 && marks != 0 {
+            state.restrict(i, marks);
+        }
+    }
+    if state.is_solved() {
+        return Hint::Solved;
+    }
+
+    if let Some(step) = state.next_step(&puzzle.inequalities()) {
+        let cell = step.placement.map(|p| p.cell)
+            .or_else(|| step.eliminations.first().map(|e| e.cell))
+            .unwrap_or_default();
+        return Hint::Deduction {
+            cell,
+            value: step.placement.map(|p| p.value),
+            eliminations: step.eliminations,
+            technique: step.technique,
+            technique_name: step.technique.name(),
+            house: step.house,
+            constraints: step.constraints,
+            peers: step.cells,
+        };
+    }
+
+    let cell = (0..cells)
+        .filter(|&i| board[i] == 0)
+        .min_by_key(|&i| state.candidates(i).count_ones())
+        .unwrap_or_default();
+    Hint::Reveal { cell, value: solution[cell] }
+}
+
+fn find_mistake(board: &Grid, pencil_marks: &[u16], solution: &Grid) -> Option<Hint> {
+    if let Some(cell) = (0..board.len()).find(|&i| board[i] != 0 && board[i] != solution[i]) {
+        return Some(Hint::Mistake { cell, mistake: MistakeKind::Entry });
+    }
+    (0..pencil_marks.len().min(board.len()))
+        .find(|&i| board[i] == 0 && pencil_marks[i] != 0 && pencil_marks[i] & (1 << (solution[i] - 1)) == 0)
+        .map(|cell| Hint::Mistake { cell, mistake: MistakeKind::PencilMark })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::futoshiki;
+    use crate::geometry::Geometry;
+    use crate::sudoku;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn puzzle_with_blanks(blanks: &[usize]) -> (Puzzle, Grid) {
+        let geometry = Geometry::classic();
+        let solution = sudoku::generate_complete_grid(&geometry, &mut ChaCha8Rng::seed_from_u64(11)).unwrap();
+        let mut grid: Vec<i8> = solution.iter().map(|&v| v as i8).collect();
+        for &i in blanks {
+            grid[i] = -1;
+        }
+        let constraints = futoshiki::generate_from_grid(&geometry, &solution);
+        (Puzzle { geometry, grid, constraints, ..Puzzle::default() }, solution)
+    }
+
+    #[test]
+    fn test_wrong_entry_is_reported() {
+        let (puzzle, solution) = puzzle_with_blanks(&[0, 1, 2]);
+        let mut entries = [-1i8; 81];
+        entries[1] = (solution[1] % 9 + 1) as i8;
+        let hint = next_hint(&puzzle, &entries, &[0; 81]);
+        assert_eq!(hint, Hint::Mistake { cell: 1, mistake: MistakeKind::Entry });
+    }
+
+    #[test]
+    fn test_wrong_pencil_mark_is_reported() {
+        let (puzzle, solution) = puzzle_with_blanks(&[0, 1, 2]);
+        let mut marks = [0u16; 81];
+        marks[2] = 0x1FF & !(1 << (solution[2] - 1));
+        let hint = next_hint(&puzzle, &[-1; 81], &marks);
+        assert_eq!(hint, Hint::Mistake { cell: 2, mistake: MistakeKind::PencilMark });
+    }
+
+    #[test]
+    fn test_deduction_and_solved() {
+        let (puzzle, solution) = puzzle_with_blanks(&[40]);
+        match next_hint(&puzzle, &[-1; 81], &[0; 81]) {
+            Hint::Deduction { cell, value, technique, .. } => {
+                assert_eq!(cell, 40);
+                assert_eq!(value, Some(solution[40]));
+                assert_eq!(technique, Technique::NakedSingle);
+            }
+            other => panic!("expected a deduction, got {:?}", other),
+        }
+
+        let mut entries = [-1i8; 81];
+        entries[40] = solution[40] as i8;
+        assert_eq!(next_hint(&puzzle, &entries, &[0; 81]), Hint::Solved);
+    }
+
+    #[test]
+    fn test_contradictory_givens_have_no_solution() {
+        // Two 1s in the top row of a Latin 3x3, with the rest of the row
+        // left to logic.
+        let puzzle = Puzzle { geometry: Geometry::latin(3), grid: vec![1, 1, -1, -1, -1, -1, -1, -1, -1], ..Puzzle::default() };
+        assert_eq!(next_hint(&puzzle, &[], &[]), Hint::NoSolution);
+        let mut entries = [-1i8; 9];
+        entries[2] = 2;
+        assert_eq!(next_hint(&puzzle, &entries, &[]), Hint::NoSolution);
+    }
+}
@@ -1,13 +1,262 @@
-mod sudoku;
-mod futoshiki;
-mod solver;
-mod generator;
+pub mod geometry;
+pub mod sudoku;
+pub mod futoshiki;
+pub mod solver;
+pub mod generator;
+pub mod logic;
+pub mod rating;
+pub mod hint;
+pub mod validate;
+pub mod session;
+pub mod candidates;
+pub mod share;
+pub mod text;
+pub mod svg;
 
 use wasm_bindgen::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use generator::GenerateError;
+use serde::Deserialize;
 
+// Every entry point returns `Result` so bad input or a failed generation
+// reaches JS as a thrown `Error` instead of trapping the wasm instance.
+// Puzzles and entries from JS go through `Puzzle::check` first.
+
+/// `size` is the side length and may be left `undefined`. The Latin
+/// difficulties ("LatinEasy", ...) take 4 to 9 and have no boxes; the others
+/// take 4, 6, 9 or 12 (Classic up to 9) with 2x2, 2x3, 3x3 or 3x4 boxes.
+/// Any other size is an error.
+#[wasm_bindgen]
+pub fn generate_puzzle(difficulty: &str, size: Option<usize>) -> Result<JsValue, JsError> {
+    let puzzle = generator::generate_with_rng(difficulty, size, &mut rand::thread_rng())?;
+    Ok(puzzle_to_js(&puzzle)?)
+}
+
+/// Reproducible variant of `generate_puzzle`: the same seed and difficulty
+/// always give the same puzzle. `seed` arrives as a JS `BigInt`.
+#[wasm_bindgen]
+pub fn generate_puzzle_seeded(difficulty: &str, seed: u64, size: Option<usize>) -> Result<JsValue, JsError> {
+    let puzzle = generator::generate_with_rng(difficulty, size, &mut ChaCha8Rng::seed_from_u64(seed))?;
+    Ok(puzzle_to_js(&puzzle)?)
+}
+
+/// Options for `generate_puzzle_budgeted`; every field may be left out.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct BudgetedOptions {
+    #[serde(flatten)]
+    generate: generator::GenerateOptions,
+    #[serde(flatten)]
+    budget: generator::Budget,
+    seed: Option<u64>,
+}
+
+/// Budgeted, cancellable generation for running in a Web Worker.
+/// `options` is `undefined` or `{ size, minimize, symmetry,
+/// symmetric_inequalities, max_cage_size, dots, all_dots, thermometers,
+/// regions, jigsaw, seed, max_millis, max_nodes }` with any field left out:
+/// `minimize` removes every clue the unique solution does not need,
+/// `max_cage_size` turns the puzzle into a killer variant, `dots` adds
+/// Kropki dots (`all_dots` marking every qualifying pair), `thermometers`
+/// is how many to grow, `regions` lists "Diagonals", "Windoku" or
+/// `{ Custom: [[cell, ...], ...] }` as extra all-different houses,
+/// `jigsaw` swaps the boxes for random irregular ones,
+/// `symmetry` is "None", "Rotational", "Horizontal", "Vertical" or
+/// "Diagonal", and `seed` makes the result reproducible.
+/// `on_progress` is called after every attempt, and every 100 ms while a
+/// jigsaw layout or clue minimisation runs, with `{ attempt, max_attempts,
+/// elapsed_ms, nodes, best }`: `attempt` counts finished attempts and `best`
+/// is the closest puzzle rejected so far for its rating, or `null`. Returning `false` (or
+/// throwing) cancels. Returns `{ puzzle, stats, fallback }`, `stats` being
+/// the solver's work on the final uniqueness check and `fallback` true when
+/// every attempt missed the clue range or rating band and the puzzle was
+/// made without them. Throws when cancelled or when the budget runs out.
+#[wasm_bindgen]
+pub fn generate_puzzle_budgeted(difficulty: &str, options: JsValue, on_progress: &js_sys::Function) -> Result<JsValue, JsError> {
+    let options: BudgetedOptions = if options.is_undefined() || options.is_null() {
+        BudgetedOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options)?
+    };
+    let mut rng = match options.seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+        None => ChaCha8Rng::from_rng(rand::thread_rng())?,
+    };
+    let candidate = generator::generate_with_budget(difficulty, &options.generate, &mut rng, options.budget, |progress| {
+        let progress = serde_wasm_bindgen::to_value(progress).unwrap_or(JsValue::NULL);
+        on_progress.call1(&JsValue::NULL, &progress).is_ok_and(|ret| ret.as_bool() != Some(false))
+    })?;
+    Ok(serde_wasm_bindgen::to_value(&candidate)?)
+}
+
+/// A puzzle passed in from JS, checked so that no cell index or value in
+/// it can reach past the board.
+fn puzzle_from_js(puzzle: JsValue) -> Result<futoshiki::Puzzle, JsError> {
+    let puzzle: futoshiki::Puzzle = serde_wasm_bindgen::from_value(puzzle)?;
+    puzzle.check()?;
+    Ok(puzzle)
+}
+
+fn puzzle_to_js(puzzle: &futoshiki::Puzzle) -> Result<JsValue, GenerateError> {
+    serde_wasm_bindgen::to_value(puzzle).map_err(|err| GenerateError::Serialization(err.to_string()))
+}
+
+/// Next logical step for the board in progress, or the first mistake.
+/// `{ kind: "no_solution" }` if no grid completes the givens.
+/// `current_entries` (Int8Array, 0/-1 = empty) and `pencil_marks`
+/// (Uint16Array, bit v-1 = candidate v, 0 = unmarked) hold one value per cell.
+#[wasm_bindgen]
+pub fn next_hint(puzzle: JsValue, current_entries: &[i8], pencil_marks: &[u16]) -> Result<JsValue, JsError> {
+    let puzzle = puzzle_from_js(puzzle)?;
+    puzzle.check_entries(current_entries)?;
+    let hint = hint::next_hint(&puzzle, current_entries, pencil_marks);
+    Ok(serde_wasm_bindgen::to_value(&hint)?)
+}
+
+/// Duplicate, inequality, cage, dot and thermometer conflicts for the
+/// givens overlaid with `entries` (Int8Array, one per cell, 0/-1 = empty),
+/// plus whether the board is complete and correct.
+#[wasm_bindgen]
+pub fn validate_board(puzzle: JsValue, entries: &[i8]) -> Result<JsValue, JsError> {
+    let puzzle = puzzle_from_js(puzzle)?;
+    puzzle.check_entries(entries)?;
+    let validation = validate::validate_board(&puzzle, entries);
+    Ok(serde_wasm_bindgen::to_value(&validation)?)
+}
+
+/// Pencil-mark candidates for the givens overlaid with `entries`
+/// (Int8Array, one per cell, 0/-1 = empty), respecting houses,
+/// inequalities and thermometers: `{ candidates, eliminations }`, where each
+/// elimination is `{ cell, value, reason }` and `reason` names the peer,
+/// inequality or thermometer.
+#[wasm_bindgen]
+pub fn candidates(puzzle: JsValue, entries: &[i8]) -> Result<JsValue, JsError> {
+    let puzzle = puzzle_from_js(puzzle)?;
+    puzzle.check_entries(entries)?;
+    let candidates = candidates::candidates(&puzzle, entries);
+    Ok(serde_wasm_bindgen::to_value(&candidates)?)
+}
+
+/// Solution of the puzzle's givens and constraints, or `null` if none.
+#[wasm_bindgen]
+pub fn solve_puzzle(puzzle: JsValue) -> Result<JsValue, JsError> {
+    let puzzle = puzzle_from_js(puzzle)?;
+    let solution = solver::solve(&puzzle.geometry, &puzzle.givens(), &puzzle.rules());
+    Ok(serde_wasm_bindgen::to_value(&solution)?)
+}
+
+/// Cells that differ between two solutions of a user-entered puzzle, or
+/// `null` if it has at most one solution.
 #[wasm_bindgen]
-pub fn generate_puzzle(difficulty: &str) -> JsValue {
-    // Placeholder
-    let puzzle = generator::generate(difficulty);
-    serde_wasm_bindgen::to_value(&puzzle).unwrap()
+pub fn ambiguous_cells(puzzle: JsValue) -> Result<JsValue, JsError> {
+    let puzzle = puzzle_from_js(puzzle)?;
+    let cells = solver::ambiguous_cells(&puzzle.geometry, &puzzle.givens(), &puzzle.rules());
+    Ok(serde_wasm_bindgen::to_value(&cells)?)
+}
+
+/// Compact URL-safe code for `puzzle`, see `share` for the format. Throws
+/// for puzzles the format cannot hold, such as killer or jigsaw puzzles.
+#[wasm_bindgen]
+pub fn encode_puzzle(puzzle: JsValue) -> Result<String, JsError> {
+    let puzzle = puzzle_from_js(puzzle)?;
+    Ok(share::encode(&puzzle)?)
+}
+
+/// Puzzle for a share code. Throws if the code is malformed.
+#[wasm_bindgen]
+pub fn decode_puzzle(code: &str) -> Result<JsValue, JsError> {
+    let puzzle = share::decode(code)?;
+    puzzle.check()?;
+    Ok(serde_wasm_bindgen::to_value(&puzzle)?)
+}
+
+/// A game in progress, owning the puzzle, its hidden solution, the
+/// player's entries and pencil marks, and the undo/redo history. Every
+/// change returns whether the board changed; given cells never do.
+#[wasm_bindgen]
+pub struct FutoshikiSession {
+    session: session::Session,
+}
+
+#[wasm_bindgen]
+impl FutoshikiSession {
+    /// Throws if the puzzle is malformed or does not have exactly one
+    /// solution.
+    #[wasm_bindgen(constructor)]
+    pub fn new(puzzle: JsValue) -> Result<FutoshikiSession, JsError> {
+        let puzzle = puzzle_from_js(puzzle)?;
+        Ok(FutoshikiSession { session: session::Session::new(puzzle)? })
+    }
+
+    /// Restores a session saved with `to_json`.
+    pub fn from_json(json: &str) -> Result<FutoshikiSession, JsError> {
+        Ok(FutoshikiSession { session: session::Session::from_json(json)? })
+    }
+
+    pub fn to_json(&self) -> Result<String, JsError> {
+        Ok(self.session.to_json()?)
+    }
+
+    pub fn puzzle(&self) -> Result<JsValue, JsError> {
+        Ok(puzzle_to_js(self.session.puzzle())?)
+    }
+
+    /// Int8Array of the player's entries, 0 = empty or given.
+    pub fn entries(&self) -> Vec<i8> {
+        self.session.entries()
+    }
+
+    /// Uint16Array of pencil marks, bit v-1 = candidate v.
+    pub fn marks(&self) -> Vec<u16> {
+        self.session.marks()
+    }
+
+    pub fn set_value(&mut self, cell: usize, value: u8) -> bool {
+        self.session.set_value(cell, value)
+    }
+
+    pub fn toggle_mark(&mut self, cell: usize, value: u8) -> bool {
+        self.session.toggle_mark(cell, value)
+    }
+
+    pub fn clear_cell(&mut self, cell: usize) -> bool {
+        self.session.clear_cell(cell)
+    }
+
+    /// Marks every empty cell with the values its peers, the inequalities
+    /// and the thermometers leave open.
+    pub fn auto_fill_candidates(&mut self) -> bool {
+        self.session.auto_fill_candidates()
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.session.undo()
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.session.redo()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.session.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.session.can_redo()
+    }
+
+    /// Cells whose entry differs from the solution.
+    pub fn mistakes(&self) -> Result<JsValue, JsError> {
+        Ok(serde_wasm_bindgen::to_value(&self.session.mistakes())?)
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.session.is_solved()
+    }
+
+    /// Same as `next_hint` for the session's board.
+    pub fn hint(&self) -> Result<JsValue, JsError> {
+        Ok(serde_wasm_bindgen::to_value(&self.session.hint())?)
+    }
 }
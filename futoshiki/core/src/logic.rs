@@ -0,0 +1,536 @@
+//! Human-style logical solver.
+//!
+//! `solver::count_solutions` searches; this module instead applies one named
+//! deduction at a time, the way a person would, and records each of them.
+//! The resulting trace tells us which techniques a puzzle needs.
+
+use serde::Serialize;
+use crate::sudoku::Grid;
+use crate::futoshiki::Inequality;
+use crate::geometry::{Geometry, House};
+use crate::solver::{filter_gt, filter_lt, get_max, get_min};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Technique {
+    NakedSingle,
+    HiddenSingle,
+    InequalityBound,
+    InequalityChain,
+    PointingPair,
+    NakedSubset,
+    HiddenSubset,
+}
+
+impl Technique {
+    /// Simplest first. The solver always retries from the top after a step.
+    pub const ALL: [Technique; 7] = [
+        Technique::NakedSingle,
+        Technique::HiddenSingle,
+        Technique::InequalityBound,
+        Technique::InequalityChain,
+        Technique::PointingPair,
+        Technique::NakedSubset,
+        Technique::HiddenSubset,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Technique::NakedSingle => "Naked Single",
+            Technique::HiddenSingle => "Hidden Single",
+            Technique::InequalityBound => "Inequality Bound",
+            Technique::InequalityChain => "Inequality Chain",
+            Technique::PointingPair => "Pointing Pair",
+            Technique::NakedSubset => "Naked Subset",
+            Technique::HiddenSubset => "Hidden Subset",
+        }
+    }
+
+    /// Score contributed by each use of the technique when rating a puzzle.
+    pub fn weight(&self) -> u32 {
+        match self {
+            Technique::NakedSingle => 1,
+            Technique::HiddenSingle => 2,
+            Technique::InequalityBound => 3,
+            Technique::InequalityChain => 8,
+            Technique::PointingPair => 10,
+            Technique::NakedSubset => 15,
+            Technique::HiddenSubset => 20,
+        }
+    }
+}
+
+/// A value in a cell: either placed or removed by a step.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct Candidate {
+    pub cell: usize,
+    pub value: u8,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Step {
+    pub technique: Technique,
+    /// Value fixed by a single. Placing a value also removes it from the
+    /// cell's peers; those removals are implied and not listed.
+    pub placement: Option<Candidate>,
+    /// Candidates removed by this step.
+    pub eliminations: Vec<Candidate>,
+    /// Cells forming the pattern that justifies the step.
+    pub cells: Vec<usize>,
+    /// House the pattern lives in, if it is tied to one.
+    pub house: Option<House>,
+    /// Inequalities the deduction relies on.
+    pub constraints: Vec<Inequality>,
+}
+
+impl Step {
+    fn new(technique: Technique) -> Self {
+        Step {
+            technique,
+            placement: None,
+            eliminations: Vec::new(),
+            cells: Vec::new(),
+            house: None,
+            constraints: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+    /// True if the steps alone fill the whole grid.
+    pub solved: bool,
+    /// Values placed so far (0 = still empty).
+    pub grid: Grid,
+}
+
+/// Pencil-mark state the deductions work on.
+#[derive(Clone)]
+pub struct LogicState {
+    geometry: Geometry,
+    values: Grid,
+    candidates: Vec<u16>,
+}
+
+impl LogicState {
+    /// Places every given of `grid` (0 = empty) and clears it from its peers.
+    pub fn new(geometry: &Geometry, grid: &Grid) -> Self {
+        let mut state = LogicState {
+            geometry: geometry.clone(),
+            values: vec![0; geometry.cells()],
+            candidates: vec![geometry.all_values(); geometry.cells()],
+        };
+        for (idx, &val) in grid.iter().enumerate() {
+            if val > 0 {
+                state.place(idx, val);
+            }
+        }
+        state
+    }
+
+    pub fn value(&self, idx: usize) -> u8 {
+        self.values[idx]
+    }
+
+    pub fn candidates(&self, idx: usize) -> u16 {
+        self.candidates[idx]
+    }
+
+    /// Keeps only the candidates of `idx` present in `mask`.
+    pub fn restrict(&mut self, idx: usize, mask: u16) {
+        self.candidates[idx] &= mask;
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.values.iter().all(|&v| v != 0) && !self.is_broken()
+    }
+
+    /// A cell without candidates means an earlier placement was wrong.
+    pub fn is_broken(&self) -> bool {
+        self.candidates.contains(&0)
+    }
+
+    pub fn apply(&mut self, step: &Step) {
+        if let Some(p) = step.placement {
+            self.place(p.cell, p.value);
+        }
+        for e in &step.eliminations {
+            self.candidates[e.cell] &= !bit(e.value);
+        }
+    }
+
+    /// The simplest deduction available, if any.
+    pub fn next_step(&self, constraints: &[Inequality]) -> Option<Step> {
+        if self.is_broken() {
+            return None;
+        }
+        Technique::ALL.iter().find_map(|&t| self.find(t, constraints))
+    }
+
+    pub fn find(&self, technique: Technique, constraints: &[Inequality]) -> Option<Step> {
+        match technique {
+            Technique::NakedSingle => self.find_naked_single(),
+            Technique::HiddenSingle => self.find_hidden_single(),
+            Technique::InequalityBound => self.find_inequality_bound(constraints),
+            Technique::InequalityChain => self.find_inequality_chain(constraints),
+            Technique::PointingPair => self.find_pointing_pair(),
+            Technique::NakedSubset => self.find_naked_subset(),
+            Technique::HiddenSubset => self.find_hidden_subset(),
+        }
+    }
+
+    fn size(&self) -> u8 {
+        self.geometry.size as u8
+    }
+
+    fn place(&mut self, idx: usize, val: u8) {
+        self.values[idx] = val;
+        self.candidates[idx] = bit(val);
+        for peer in self.geometry.peers(idx) {
+            self.candidates[peer] &= !bit(val);
+        }
+    }
+
+    fn unset_cells(&self, house: House) -> Vec<usize> {
+        house.cells(&self.geometry).into_iter().filter(|&i| self.values[i] == 0).collect()
+    }
+
+    fn find_naked_single(&self) -> Option<Step> {
+        let idx = (0..self.values.len()).find(|&i| self.values[i] == 0 && self.candidates[i].count_ones() == 1)?;
+        let mut step = Step::new(Technique::NakedSingle);
+        step.placement = Some(Candidate { cell: idx, value: get_min(self.candidates[idx]) });
+        step.cells.push(idx);
+        Some(step)
+    }
+
+    fn find_hidden_single(&self) -> Option<Step> {
+        for house in self.geometry.houses() {
+            let unset = self.unset_cells(house);
+            for val in 1..=self.size() {
+                let spots: Vec<usize> = unset.iter().copied()
+                    .filter(|&i| self.candidates[i] & bit(val) != 0)
+                    .collect();
+                if spots.len() == 1 {
+                    let mut step = Step::new(Technique::HiddenSingle);
+                    step.placement = Some(Candidate { cell: spots[0], value: val });
+                    step.cells.push(spots[0]);
+                    step.house = Some(house);
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+
+    fn find_inequality_bound(&self, constraints: &[Inequality]) -> Option<Step> {
+        for ineq in constraints {
+            let da = self.candidates[ineq.a];
+            let db = self.candidates[ineq.b];
+
+            // a < b: a stays below max(b), b stays above min(a)
+            let removed_a = da & !filter_lt(da, get_max(db));
+            let removed_b = db & !filter_gt(db, get_min(da));
+            if removed_a == 0 && removed_b == 0 {
+                continue;
+            }
+
+            let mut step = Step::new(Technique::InequalityBound);
+            step.eliminations.extend(eliminations(ineq.a, removed_a));
+            step.eliminations.extend(eliminations(ineq.b, removed_b));
+            step.cells = vec![ineq.a, ineq.b];
+            step.constraints.push(*ineq);
+            return Some(step);
+        }
+        None
+    }
+
+    /// Cells known to be smaller than `x` (or larger, if `above`) all hold
+    /// distinct values when they share a house, so `x` must lie beyond the
+    /// k-th smallest (largest) of their combined candidates.
+    fn find_inequality_chain(&self, constraints: &[Inequality]) -> Option<Step> {
+        let all = self.geometry.all_values();
+        for x in (0..self.values.len()).filter(|&i| self.values[i] == 0) {
+            for above in [false, true] {
+                let parents = reach(x, constraints, above, self.values.len());
+                for house in self.geometry.houses() {
+                    let set: Vec<usize> = house.cells(&self.geometry).into_iter()
+                        .filter(|&i| parents[i].is_some())
+                        .collect();
+                    if set.len() < 2 {
+                        continue;
+                    }
+
+                    let union = set.iter().fold(0, |acc, &i| acc | self.candidates[i]);
+                    let mut values: Vec<u8> = (1..=self.size()).filter(|&v| union & bit(v) != 0).collect();
+                    if values.len() < set.len() {
+                        continue;
+                    }
+                    if above {
+                        values.reverse();
+                    }
+                    let bound = values[set.len() - 1];
+                    let removed = if above {
+                        self.candidates[x] & !filter_lt(all, bound)
+                    } else {
+                        self.candidates[x] & !filter_gt(all, bound)
+                    };
+                    if removed == 0 {
+                        continue;
+                    }
+
+                    let mut step = Step::new(Technique::InequalityChain);
+                    step.eliminations = eliminations(x, removed);
+                    step.cells = set.clone();
+                    step.cells.push(x);
+                    step.house = Some(house);
+                    for &start in &set {
+                        let mut cur = start;
+                        while let Some(ci) = parents[cur] {
+                            let ineq = constraints[ci];
+                            if !step.constraints.contains(&ineq) {
+                                step.constraints.push(ineq);
+                            }
+                            cur = if above { ineq.a } else { ineq.b };
+                        }
+                    }
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+
+    /// Locked candidates: a value confined to one line inside a box (or to
+    /// one box inside a line) can be removed from the rest of the other house.
+    fn find_pointing_pair(&self) -> Option<Step> {
+        for house in self.geometry.houses() {
+            let unset = self.unset_cells(house);
+            for val in 1..=self.size() {
+                let spots: Vec<usize> = unset.iter().copied()
+                    .filter(|&i| self.candidates[i] & bit(val) != 0)
+                    .collect();
+                if spots.len() < 2 {
+                    continue;
+                }
+
+                let targets = match house {
+                    House::Box(_) => vec![
+                        House::Row(self.geometry.row(spots[0])),
+                        House::Column(self.geometry.col(spots[0])),
+                    ],
+                    _ => self.geometry.box_of(spots[0]).map(House::Box).into_iter().collect(),
+                };
+                for target in targets {
+                    if !spots.iter().all(|&i| target.contains(&self.geometry, i)) {
+                        continue;
+                    }
+                    let removed: Vec<Candidate> = self.unset_cells(target).into_iter()
+                        .filter(|&i| !house.contains(&self.geometry, i) && self.candidates[i] & bit(val) != 0)
+                        .map(|cell| Candidate { cell, value: val })
+                        .collect();
+                    if removed.is_empty() {
+                        continue;
+                    }
+
+                    let mut step = Step::new(Technique::PointingPair);
+                    step.eliminations = removed;
+                    step.cells = spots.clone();
+                    step.house = Some(house);
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+
+    /// n cells of a house sharing exactly n candidates own those values.
+    fn find_naked_subset(&self) -> Option<Step> {
+        for house in self.geometry.houses() {
+            let unset = self.unset_cells(house);
+            for n in 2..=4.min(unset.len().saturating_sub(1)) {
+                for subset in combinations(&unset, n) {
+                    let union = subset.iter().fold(0, |acc, &i| acc | self.candidates[i]);
+                    if union.count_ones() as usize != n {
+                        continue;
+                    }
+                    let removed: Vec<Candidate> = unset.iter()
+                        .filter(|i| !subset.contains(i))
+                        .flat_map(|&i| eliminations(i, self.candidates[i] & union))
+                        .collect();
+                    if removed.is_empty() {
+                        continue;
+                    }
+
+                    let mut step = Step::new(Technique::NakedSubset);
+                    step.eliminations = removed;
+                    step.cells = subset;
+                    step.house = Some(house);
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+
+    /// n values of a house confined to the same n cells push every other
+    /// candidate out of those cells.
+    fn find_hidden_subset(&self) -> Option<Step> {
+        for house in self.geometry.houses() {
+            let unset = self.unset_cells(house);
+            let open: Vec<usize> = (1..=self.size() as usize)
+                .filter(|&v| unset.iter().any(|&i| self.candidates[i] & bit(v as u8) != 0))
+                .collect();
+            for n in 2..=4.min(unset.len().saturating_sub(1)) {
+                for values in combinations(&open, n) {
+                    let mask = values.iter().fold(0, |acc, &v| acc | bit(v as u8));
+                    let spots: Vec<usize> = unset.iter().copied()
+                        .filter(|&i| self.candidates[i] & mask != 0)
+                        .collect();
+                    if spots.len() != n {
+                        continue;
+                    }
+                    let removed: Vec<Candidate> = spots.iter()
+                        .flat_map(|&i| eliminations(i, self.candidates[i] & !mask))
+                        .collect();
+                    if removed.is_empty() {
+                        continue;
+                    }
+
+                    let mut step = Step::new(Technique::HiddenSubset);
+                    step.eliminations = removed;
+                    step.cells = spots;
+                    step.house = Some(house);
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Applies deductions until the grid is full or no technique applies.
+pub fn solve(geometry: &Geometry, grid: &Grid, constraints: &[Inequality]) -> Trace {
+    let mut state = LogicState::new(geometry, grid);
+    let mut steps = Vec::new();
+    while !state.is_solved() {
+        match state.next_step(constraints) {
+            Some(step) => {
+                state.apply(&step);
+                steps.push(step);
+            }
+            None => break,
+        }
+    }
+    Trace { steps, solved: state.is_solved(), grid: state.values }
+}
+
+fn bit(val: u8) -> u16 {
+    1 << (val - 1)
+}
+
+fn eliminations(cell: usize, mask: u16) -> Vec<Candidate> {
+    (1..=16)
+        .filter(|&v| mask & bit(v) != 0)
+        .map(|value| Candidate { cell, value })
+        .collect()
+}
+
+/// For every cell transitively below `x` (above, if `above`), the index of
+/// the constraint leading one step closer to `x`. `x` itself stays `None`.
+fn reach(x: usize, constraints: &[Inequality], above: bool, cells: usize) -> Vec<Option<usize>> {
+    let mut parents = vec![None; cells];
+    let mut visited = vec![false; cells];
+    visited[x] = true;
+    let mut queue = vec![x];
+    while let Some(cur) = queue.pop() {
+        for (ci, ineq) in constraints.iter().enumerate() {
+            let (near, far) = if above { (ineq.a, ineq.b) } else { (ineq.b, ineq.a) };
+            if near == cur && !visited[far] {
+                visited[far] = true;
+                parents[far] = Some(ci);
+                queue.push(far);
+            }
+        }
+    }
+    parents
+}
+
+fn combinations(items: &[usize], n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![Vec::new()];
+    }
+    let mut out = Vec::new();
+    for i in 0..items.len() {
+        for mut rest in combinations(&items[i + 1..], n - 1) {
+            rest.insert(0, items[i]);
+            out.push(rest);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::futoshiki;
+    use crate::sudoku;
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    fn full_grid(geometry: &Geometry, seed: u64) -> Grid {
+        sudoku::generate_complete_grid(geometry, &mut ChaCha8Rng::seed_from_u64(seed)).unwrap()
+    }
+
+    #[test]
+    fn test_steps_never_remove_solution_values() {
+        for geometry in [Geometry::classic(), Geometry::latin(7)] {
+            for seed in 0..10 {
+                let solution = full_grid(&geometry, seed);
+                let constraints = futoshiki::generate_from_grid(&geometry, &solution);
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                let mut grid = vec![0u8; geometry.cells()];
+                for idx in (0..geometry.cells()).choose_multiple(&mut rng, 10) {
+                    grid[idx] = solution[idx];
+                }
+
+                let mut state = LogicState::new(&geometry, &grid);
+                while let Some(step) = state.next_step(&constraints) {
+                    if let Some(p) = step.placement {
+                        assert_eq!(p.value, solution[p.cell], "{:?} placed a wrong value", step.technique);
+                    }
+                    for e in &step.eliminations {
+                        assert_ne!(e.value, solution[e.cell], "{:?} removed the solution value", step.technique);
+                    }
+                    state.apply(&step);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_easy_puzzle_solves_logically() {
+        let geometry = Geometry::classic();
+        let solution = full_grid(&geometry, 7);
+        let constraints = futoshiki::generate_from_grid(&geometry, &solution);
+        let mut grid = solution.clone();
+        for idx in (0..81).step_by(2) {
+            grid[idx] = 0;
+        }
+        let trace = solve(&geometry, &grid, &constraints);
+        assert!(trace.solved);
+        assert_eq!(trace.grid, solution);
+        assert!(trace.steps.iter().any(|s| s.technique == Technique::InequalityBound));
+    }
+
+    #[test]
+    fn test_hidden_single_in_row() {
+        // Row 0 holds 1..8; the last cell can only take the 9.
+        let mut grid = vec![0u8; 81];
+        for (c, cell) in grid.iter_mut().take(8).enumerate() {
+            *cell = c as u8 + 1;
+        }
+        let state = LogicState::new(&Geometry::classic(), &grid);
+        let step = state.next_step(&[]).unwrap();
+        assert_eq!(step.placement, Some(Candidate { cell: 8, value: 9 }));
+    }
+}
@@ -0,0 +1,96 @@
+//! Difficulty rating from the logical solver's trace.
+//!
+//! A puzzle is rated by the hardest technique it needs and by the summed
+//! weight of every step, rather than by how many clues it has.
+
+use serde::Serialize;
+use crate::futoshiki::Puzzle;
+use crate::logic::{self, Technique};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct Rating {
+    /// Hardest technique used, `None` if the clues already fill the grid.
+    pub hardest: Option<Technique>,
+    pub steps: usize,
+    /// Sum of `Technique::weight` over all steps.
+    pub score: u32,
+    /// False if the techniques get stuck and the puzzle needs guessing.
+    pub solved: bool,
+}
+
+/// Range of ratings a difficulty accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RatingBand {
+    pub min_score: u32,
+    pub max_score: u32,
+    /// The puzzle must need at least this technique.
+    pub min_technique: Technique,
+    /// The puzzle must not need anything harder.
+    pub max_technique: Technique,
+    pub allow_guessing: bool,
+}
+
+impl RatingBand {
+    pub fn contains(&self, rating: &Rating) -> bool {
+        if !rating.solved && !self.allow_guessing {
+            return false;
+        }
+        let hardest = rating.hardest.unwrap_or(Technique::NakedSingle);
+        rating.score >= self.min_score
+            && rating.score <= self.max_score
+            && hardest >= self.min_technique
+            && hardest <= self.max_technique
+    }
+
+    /// How far `rating` lies outside the band, for picking the closest of
+    /// several rejected puzzles: the number of technique and guessing
+    /// conditions it breaks, then how far its score is out of range.
+    /// `(0, 0)` exactly when the band contains it.
+    pub fn distance(&self, rating: &Rating) -> (usize, u32) {
+        let hardest = rating.hardest.unwrap_or(Technique::NakedSingle);
+        let broken = [
+            !rating.solved && !self.allow_guessing,
+            hardest < self.min_technique,
+            hardest > self.max_technique,
+        ];
+        let score = self.min_score.saturating_sub(rating.score) + rating.score.saturating_sub(self.max_score);
+        (broken.iter().filter(|&&b| b).count(), score)
+    }
+}
+
+pub fn rate(puzzle: &Puzzle) -> Rating {
+    let trace = logic::solve(&puzzle.geometry, &puzzle.givens(), &puzzle.inequalities());
+    Rating {
+        hardest: trace.steps.iter().map(|s| s.technique).max(),
+        steps: trace.steps.len(),
+        score: trace.steps.iter().map(|s| s.technique.weight()).sum(),
+        solved: trace.solved,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::futoshiki;
+    use crate::geometry::Geometry;
+    use crate::sudoku;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_rate_counts_steps_and_weights() {
+        let geometry = Geometry::classic();
+        let solution = sudoku::generate_complete_grid(&geometry, &mut ChaCha8Rng::seed_from_u64(3)).unwrap();
+        let mut grid: Vec<i8> = solution.iter().map(|&v| v as i8).collect();
+        grid[0] = -1;
+        grid[40] = -1;
+        let constraints = futoshiki::generate_from_grid(&geometry, &solution);
+        let puzzle = Puzzle { geometry, grid, constraints, ..Puzzle::default() };
+
+        let rating = rate(&puzzle);
+        assert!(rating.solved);
+        assert_eq!(rating.steps, 2);
+        assert_eq!(rating.hardest, Some(Technique::NakedSingle));
+        assert_eq!(rating.score, 2);
+    }
+}
@@ -0,0 +1,316 @@
+//! A game in progress: the puzzle, its solution, the player's entries and
+//! pencil marks, and the undo/redo history.
+//!
+//! Every operation that changes the board records the cells it touched as
+//! one move, so a single undo reverts it whole, even an auto-fill that
+//! marks every cell. The whole session round-trips through JSON.
+
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use crate::candidates;
+use crate::futoshiki::{Puzzle, PuzzleError};
+use crate::hint::{self, Hint};
+use crate::solver;
+use crate::sudoku::Grid;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionError {
+    /// The puzzle itself is malformed, see `Puzzle::check`.
+    InvalidPuzzle(PuzzleError),
+    /// The givens and constraints admit no solution or more than one.
+    NoUniqueSolution,
+    /// A saved session could not be read or written.
+    Json(String),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::InvalidPuzzle(err) => write!(f, "invalid puzzle: {}", err),
+            SessionError::NoUniqueSolution => write!(f, "puzzle has no unique solution"),
+            SessionError::Json(msg) => write!(f, "invalid session: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// What the player has put in one cell.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+struct CellState {
+    /// 0 = empty.
+    value: u8,
+    /// Pencil marks, bit v-1 = candidate v.
+    marks: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct Change {
+    cell: usize,
+    before: CellState,
+    after: CellState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Session {
+    puzzle: Puzzle,
+    solution: Grid,
+    cells: Vec<CellState>,
+    undo: Vec<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+}
+
+impl Session {
+    /// Starts an empty board for `puzzle`, which must have exactly one
+    /// solution.
+    pub fn new(puzzle: Puzzle) -> Result<Self, SessionError> {
+        puzzle.check().map_err(SessionError::InvalidPuzzle)?;
+        let mut solutions = solver::find_solutions(&puzzle.geometry, &puzzle.givens(), &puzzle.rules(), 2);
+        if solutions.len() != 1 {
+            return Err(SessionError::NoUniqueSolution);
+        }
+        let cells = vec![CellState::default(); puzzle.geometry.cells()];
+        Ok(Session { solution: solutions.remove(0), puzzle, cells, undo: Vec::new(), redo: Vec::new() })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SessionError> {
+        let session: Session = serde_json::from_str(json).map_err(|err| SessionError::Json(err.to_string()))?;
+        session.check()?;
+        Ok(session)
+    }
+
+    /// Rejects a saved session whose board, solution or history does not
+    /// fit its puzzle, so no later move can index past the board.
+    fn check(&self) -> Result<(), SessionError> {
+        self.puzzle.check().map_err(SessionError::InvalidPuzzle)?;
+        let geometry = &self.puzzle.geometry;
+        let invalid = |msg: &str| Err(SessionError::Json(msg.to_string()));
+        if self.cells.len() != geometry.cells() || self.solution.len() != self.cells.len() {
+            return invalid("board size does not match the puzzle");
+        }
+        let in_range = |value: u8| value as usize <= geometry.size;
+        let valid_state = |state: &CellState| in_range(state.value) && state.marks & !geometry.all_values() == 0;
+        if !self.solution.iter().all(|&value| value != 0 && in_range(value)) || !self.cells.iter().all(valid_state) {
+            return invalid("value out of range");
+        }
+        let valid_change = |change: &Change| change.cell < self.cells.len() && valid_state(&change.before) && valid_state(&change.after);
+        if !self.undo.iter().chain(&self.redo).flatten().all(valid_change) {
+            return invalid("history refers to an invalid cell or value");
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String, SessionError> {
+        serde_json::to_string(self).map_err(|err| SessionError::Json(err.to_string()))
+    }
+
+    pub fn puzzle(&self) -> &Puzzle {
+        &self.puzzle
+    }
+
+    /// The player's entries, one per cell, 0 = empty or given.
+    pub fn entries(&self) -> Vec<i8> {
+        self.cells.iter().map(|cell| cell.value as i8).collect()
+    }
+
+    /// Pencil marks, one mask per cell.
+    pub fn marks(&self) -> Vec<u16> {
+        self.cells.iter().map(|cell| cell.marks).collect()
+    }
+
+    /// Givens and entries together, 0 = empty.
+    pub fn board(&self) -> Grid {
+        self.puzzle.board(&self.entries())
+    }
+
+    /// Enters `value` (1..=size) in `cell`, clearing its marks. False if
+    /// the cell is a given or nothing changed.
+    pub fn set_value(&mut self, cell: usize, value: u8) -> bool {
+        if !(1..=self.puzzle.geometry.size as u8).contains(&value) {
+            return false;
+        }
+        self.apply(vec![(cell, CellState { value, marks: 0 })])
+    }
+
+    /// Adds or removes pencil mark `value` in an empty `cell`.
+    pub fn toggle_mark(&mut self, cell: usize, value: u8) -> bool {
+        if !(1..=self.puzzle.geometry.size as u8).contains(&value) {
+            return false;
+        }
+        let Some(&state) = self.cells.get(cell) else {
+            return false;
+        };
+        if state.value != 0 {
+            return false;
+        }
+        self.apply(vec![(cell, CellState { marks: state.marks ^ 1 << (value - 1), ..state })])
+    }
+
+    /// Removes the entry and every mark from `cell`.
+    pub fn clear_cell(&mut self, cell: usize) -> bool {
+        self.apply(vec![(cell, CellState::default())])
+    }
+
+    /// Marks every empty cell with the values its peers, the inequalities
+    /// and the thermometers leave open, see `candidates::candidates`.
+    pub fn auto_fill_candidates(&mut self) -> bool {
+        let board = self.board();
+        let marks = candidates::candidates(&self.puzzle, &self.entries()).candidates;
+        let updates = (0..board.len())
+            .filter(|&idx| board[idx] == 0)
+            .map(|idx| (idx, CellState { value: 0, marks: marks[idx] }))
+            .collect();
+        self.apply(updates)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Reverts the last move. False if there is none.
+    pub fn undo(&mut self) -> bool {
+        let Some(changes) = self.undo.pop() else {
+            return false;
+        };
+        for change in changes.iter().rev() {
+            self.cells[change.cell] = change.before;
+        }
+        self.redo.push(changes);
+        true
+    }
+
+    /// Replays the last undone move. False if there is none.
+    pub fn redo(&mut self) -> bool {
+        let Some(changes) = self.redo.pop() else {
+            return false;
+        };
+        for change in &changes {
+            self.cells[change.cell] = change.after;
+        }
+        self.undo.push(changes);
+        true
+    }
+
+    /// Cells whose entry differs from the solution.
+    pub fn mistakes(&self) -> Vec<usize> {
+        (0..self.cells.len())
+            .filter(|&idx| self.cells[idx].value != 0 && self.cells[idx].value != self.solution[idx])
+            .collect()
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.board() == self.solution
+    }
+
+    /// Next hint for the board as it stands, see `hint::next_hint`.
+    pub fn hint(&self) -> Hint {
+        hint::next_hint(&self.puzzle, &self.entries(), &self.marks())
+    }
+
+    /// Applies `updates` to non-given cells as one move, dropping the redo
+    /// history. False if no cell changed.
+    fn apply(&mut self, updates: Vec<(usize, CellState)>) -> bool {
+        let changes: Vec<Change> = updates.into_iter()
+            .filter(|&(cell, _)| cell < self.cells.len() && self.puzzle.grid[cell] <= 0)
+            .filter(|&(cell, after)| self.cells[cell] != after)
+            .map(|(cell, after)| Change { cell, before: self.cells[cell], after })
+            .collect();
+        if changes.is_empty() {
+            return false;
+        }
+        for change in &changes {
+            self.cells[change.cell] = change.after;
+        }
+        self.undo.push(changes);
+        self.redo.clear();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::futoshiki::Inequality;
+    use crate::generator;
+
+    fn session() -> Session {
+        Session::new(generator::generate_seeded("LatinNormal", 2).unwrap()).unwrap()
+    }
+
+    fn empty_cell(session: &Session) -> usize {
+        session.puzzle().grid.iter().position(|&v| v <= 0).unwrap()
+    }
+
+    #[test]
+    fn test_moves_undo_and_redo() {
+        let mut session = session();
+        let cell = empty_cell(&session);
+        let given = session.puzzle().grid.iter().position(|&v| v > 0).unwrap();
+        assert!(!session.set_value(given, 1));
+        assert!(!session.can_undo());
+
+        assert!(session.toggle_mark(cell, 2));
+        assert!(session.toggle_mark(cell, 3));
+        assert_eq!(session.marks()[cell], 0b110);
+        assert!(session.set_value(cell, 1));
+        assert_eq!((session.entries()[cell], session.marks()[cell]), (1, 0));
+        assert!(!session.set_value(cell, 1));
+
+        assert!(session.undo());
+        assert_eq!((session.entries()[cell], session.marks()[cell]), (0, 0b110));
+        assert!(session.undo() && session.redo());
+        assert_eq!(session.marks()[cell], 0b110);
+
+        // A new move drops what was left to redo.
+        assert!(session.clear_cell(cell));
+        assert!(!session.can_redo() && !session.redo());
+        assert_eq!(session.marks()[cell], 0);
+    }
+
+    #[test]
+    fn test_auto_fill_is_one_move() {
+        let mut session = session();
+        let board = session.board();
+        assert!(session.auto_fill_candidates());
+        let geometry = &session.puzzle().geometry;
+        for idx in (0..geometry.cells()).filter(|&i| board[i] == 0) {
+            let marks = session.marks()[idx];
+            assert_ne!(marks & 1 << (session.solution[idx] - 1), 0);
+            assert!(geometry.peers(idx).iter().all(|&j| board[j] == 0 || marks & 1 << (board[j] - 1) == 0));
+        }
+        assert!(!session.auto_fill_candidates());
+        assert!(session.undo());
+        assert!(session.marks().iter().all(|&m| m == 0));
+    }
+
+    #[test]
+    fn test_solving_and_saving() {
+        let mut session = session();
+        let cell = empty_cell(&session);
+        let wrong = session.solution[cell] % session.puzzle().geometry.size as u8 + 1;
+        session.set_value(cell, wrong);
+        assert_eq!(session.mistakes(), vec![cell]);
+
+        let restored = Session::from_json(&session.to_json().unwrap()).unwrap();
+        assert_eq!(restored, session);
+        assert!(matches!(Session::from_json("{}"), Err(SessionError::Json(_))));
+
+        // A tampered history must not reach past the board.
+        let mut tampered = session.clone();
+        tampered.undo[0][0].cell = 999;
+        assert!(matches!(Session::from_json(&tampered.to_json().unwrap()), Err(SessionError::Json(_))));
+        let mut tampered = session.clone();
+        tampered.puzzle.constraints.push(Inequality { a: 0, b: 99 });
+        assert!(matches!(Session::from_json(&tampered.to_json().unwrap()), Err(SessionError::InvalidPuzzle(_))));
+
+        for idx in 0..session.cells.len() {
+            session.set_value(idx, session.solution[idx]);
+        }
+        assert!(session.mistakes().is_empty() && session.is_solved());
+        assert_eq!(session.hint(), Hint::Solved);
+    }
+}
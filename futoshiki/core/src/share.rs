@@ -0,0 +1,294 @@
+//! Compact, URL-safe puzzle codes for sharing.
+//!
+//! A code is unpadded URL-safe base64 of the following bytes:
+//!
+//! | bytes         | content                                                  |
+//! |---------------|----------------------------------------------------------|
+//! | 1             | format version (`VERSION`)                               |
+//! | 1             | board size                                               |
+//! | 1             | box rows in the high nibble, box cols in the low (0 = none) |
+//! | cells / 2     | clue per cell, one nibble each, high nibble first (0 = empty) |
+//! | pairs / 4     | two bits per adjacent pair, horizontal pairs first, then vertical |
+//! | 2             | Fletcher-16 checksum of everything before it             |
+//!
+//! Pair bits are 0 for no inequality, 1 when the left/upper cell is the
+//! smaller one and 2 when it is the larger one.
+
+use std::fmt;
+use crate::futoshiki::{Inequality, Puzzle};
+use crate::geometry::{BoxShape, Geometry, MAX_SIZE};
+
+pub const VERSION: u8 = 1;
+
+/// Why a code could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Not a base64url character, at this position in the code.
+    InvalidCharacter(usize),
+    /// The code ends before the data its header announces.
+    Truncated,
+    /// Bytes remain after the checksum.
+    TrailingData,
+    UnsupportedVersion(u8),
+    /// The size or box shape does not describe a supported board.
+    InvalidGeometry,
+    ChecksumMismatch,
+    /// A clue larger than the board size.
+    InvalidClue { cell: usize, value: u8 },
+    /// The reserved direction value 3 for an adjacent pair.
+    InvalidInequality { pair: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidCharacter(pos) => write!(f, "invalid character at position {}", pos),
+            DecodeError::Truncated => write!(f, "code is truncated"),
+            DecodeError::TrailingData => write!(f, "unexpected data after the checksum"),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported code version {}", v),
+            DecodeError::InvalidGeometry => write!(f, "invalid board size or box shape"),
+            DecodeError::ChecksumMismatch => write!(f, "checksum mismatch"),
+            DecodeError::InvalidClue { cell, value } => write!(f, "clue {} in cell {} is out of range", value, cell),
+            DecodeError::InvalidInequality { pair } => write!(f, "invalid inequality for pair {}", pair),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Why a puzzle has no share code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    /// Killer cages, Kropki dots, thermometers, extra regions or jigsaw
+    /// boxes, none of which the format can hold.
+    VariantRules,
+    /// An inequality between cells that are not orthogonal neighbours.
+    /// `index` points into `Puzzle::constraints`.
+    NonAdjacentInequality { index: usize },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::VariantRules => write!(f, "share codes cannot hold cages, dots, thermometers, extra regions or jigsaw boxes"),
+            EncodeError::NonAdjacentInequality { index } => write!(f, "inequality {} is not between neighbouring cells", index),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Encodes `puzzle` as a share code. Only plain Futoshiki on a regular
+/// board fits: inequalities must join orthogonal neighbours, and a puzzle
+/// with killer cages, Kropki dots, thermometers, extra regions or jigsaw
+/// boxes is refused rather than shared without them.
+pub fn encode(puzzle: &Puzzle) -> Result<String, EncodeError> {
+    let geometry = &puzzle.geometry;
+    if !puzzle.cages.is_empty() || !puzzle.dots.is_empty() || puzzle.all_dots || !puzzle.thermos.is_empty()
+        || !geometry.regions.is_empty() || geometry.jigsaw.is_some()
+    {
+        return Err(EncodeError::VariantRules);
+    }
+    let pairs: Vec<(usize, usize)> = geometry.neighbour_pairs().collect();
+    if let Some(index) = puzzle.constraints.iter()
+        .position(|ineq| !pairs.contains(&(ineq.a, ineq.b)) && !pairs.contains(&(ineq.b, ineq.a)))
+    {
+        return Err(EncodeError::NonAdjacentInequality { index });
+    }
+
+    let mut bytes = vec![VERSION, geometry.size as u8];
+    bytes.push(geometry.boxes.map_or(0, |b| ((b.rows as u8) << 4) | b.cols as u8));
+
+    let givens = puzzle.givens();
+    for pair in givens.chunks(2) {
+        bytes.push((pair[0] << 4) | pair.get(1).copied().unwrap_or(0));
+    }
+
+    let mut directions = vec![0u8; pairs.len().div_ceil(4)];
+    for (k, &(first, second)) in pairs.iter().enumerate() {
+        let bits = if puzzle.constraints.contains(&Inequality { a: first, b: second }) {
+            1
+        } else if puzzle.constraints.contains(&Inequality { a: second, b: first }) {
+            2
+        } else {
+            0
+        };
+        directions[k / 4] |= bits << (6 - 2 * (k % 4));
+    }
+    bytes.extend(directions);
+
+    let checksum = fletcher16(&bytes);
+    bytes.extend(checksum.to_be_bytes());
+    Ok(to_base64(&bytes))
+}
+
+/// Decodes a share code. Inequalities come back in pair order: horizontal
+/// pairs row by row, then vertical ones.
+pub fn decode(code: &str) -> Result<Puzzle, DecodeError> {
+    let bytes = from_base64(code.trim())?;
+    let header = bytes.get(..3).ok_or(DecodeError::Truncated)?;
+    if header[0] != VERSION {
+        return Err(DecodeError::UnsupportedVersion(header[0]));
+    }
+    let geometry = decode_geometry(header[1], header[2])?;
+
+    let cells = geometry.cells();
+    let pairs: Vec<(usize, usize)> = geometry.neighbour_pairs().collect();
+    let clue_len = cells.div_ceil(2);
+    let direction_len = pairs.len().div_ceil(4);
+    let body_len = 3 + clue_len + direction_len;
+    if bytes.len() < body_len + 2 {
+        return Err(DecodeError::Truncated);
+    }
+    if bytes.len() > body_len + 2 {
+        return Err(DecodeError::TrailingData);
+    }
+    let checksum = u16::from_be_bytes([bytes[body_len], bytes[body_len + 1]]);
+    if fletcher16(&bytes[..body_len]) != checksum {
+        return Err(DecodeError::ChecksumMismatch);
+    }
+
+    let clues = &bytes[3..3 + clue_len];
+    let mut grid = Vec::with_capacity(cells);
+    for cell in 0..cells {
+        let value = (clues[cell / 2] >> (4 - 4 * (cell % 2))) & 0xF;
+        if value as usize > geometry.size {
+            return Err(DecodeError::InvalidClue { cell, value });
+        }
+        grid.push(if value == 0 { -1 } else { value as i8 });
+    }
+
+    let directions = &bytes[3 + clue_len..body_len];
+    let mut constraints = Vec::new();
+    for (pair, &(first, second)) in pairs.iter().enumerate() {
+        match (directions[pair / 4] >> (6 - 2 * (pair % 4))) & 0b11 {
+            0 => {}
+            1 => constraints.push(Inequality { a: first, b: second }),
+            2 => constraints.push(Inequality { a: second, b: first }),
+            _ => return Err(DecodeError::InvalidInequality { pair }),
+        }
+    }
+
+    Ok(Puzzle { geometry, grid, constraints, ..Puzzle::default() })
+}
+
+fn decode_geometry(size: u8, boxes: u8) -> Result<Geometry, DecodeError> {
+    let size = size as usize;
+    if !(2..=MAX_SIZE).contains(&size) {
+        return Err(DecodeError::InvalidGeometry);
+    }
+    if boxes == 0 {
+        return Ok(Geometry::latin(size));
+    }
+    let shape = BoxShape { rows: (boxes >> 4) as usize, cols: (boxes & 0xF) as usize };
+    if shape.rows * shape.cols != size {
+        return Err(DecodeError::InvalidGeometry);
+    }
+    Ok(Geometry { size, boxes: Some(shape), ..Geometry::latin(size) })
+}
+
+fn fletcher16(data: &[u8]) -> u16 {
+    let (mut sum1, mut sum2) = (0u16, 0u16);
+    for &byte in data {
+        sum1 = (sum1 + byte as u16) % 255;
+        sum2 = (sum2 + sum1) % 255;
+    }
+    (sum2 << 8) | sum1
+}
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn to_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let word = (chunk[0] as u32) << 16
+            | (chunk.get(1).copied().unwrap_or(0) as u32) << 8
+            | chunk.get(2).copied().unwrap_or(0) as u32;
+        for k in 0..=chunk.len() {
+            out.push(ALPHABET[(word >> (18 - 6 * k) & 0x3F) as usize] as char);
+        }
+    }
+    out
+}
+
+fn from_base64(code: &str) -> Result<Vec<u8>, DecodeError> {
+    let mut bytes = Vec::with_capacity(code.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for (pos, ch) in code.bytes().enumerate() {
+        let value = ALPHABET.iter().position(|&a| a == ch).ok_or(DecodeError::InvalidCharacter(pos))?;
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::futoshiki::Cage;
+    use crate::generator::{self, Budget, GenerateOptions};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn sorted(mut constraints: Vec<Inequality>) -> Vec<Inequality> {
+        constraints.sort_by_key(|c| (c.a, c.b));
+        constraints
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (difficulty, size) in [("Hard", 9), ("Easy", 6), ("Easy", 12), ("LatinNormal", 5)] {
+            let puzzle = generator::generate_with_rng(difficulty, Some(size), &mut ChaCha8Rng::seed_from_u64(3)).unwrap();
+            let code = encode(&puzzle).unwrap();
+            assert!(code.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+
+            let decoded = decode(&code).unwrap();
+            assert_eq!(decoded.geometry, puzzle.geometry);
+            assert_eq!(decoded.grid, puzzle.grid);
+            assert_eq!(sorted(decoded.constraints), sorted(puzzle.constraints));
+        }
+    }
+
+    #[test]
+    fn test_malformed_codes_are_rejected() {
+        let puzzle = generator::generate_seeded("Normal", 1).unwrap();
+        let code = encode(&puzzle).unwrap();
+
+        assert_eq!(decode(""), Err(DecodeError::Truncated));
+        assert_eq!(decode(&code[..code.len() - 4]), Err(DecodeError::Truncated));
+        assert_eq!(decode(&format!("{}AAAA", code)), Err(DecodeError::TrailingData));
+        assert_eq!(decode(&code.replacen('A', "*", 1)), Err(DecodeError::InvalidCharacter(code.find('A').unwrap())));
+
+        // Changing one clue character breaks the checksum.
+        let mut tampered = code.clone().into_bytes();
+        tampered[10] = if tampered[10] == b'B' { b'C' } else { b'B' };
+        assert_eq!(decode(std::str::from_utf8(&tampered).unwrap()), Err(DecodeError::ChecksumMismatch));
+
+        let mut bytes = from_base64(&code).unwrap();
+        bytes[0] = 9;
+        assert_eq!(decode(&to_base64(&bytes)), Err(DecodeError::UnsupportedVersion(9)));
+
+        // Nibbles could carry sizes up to 15, but boards stop at MAX_SIZE.
+        for size in [0, 1, MAX_SIZE as u8 + 1, 15] {
+            assert_eq!(decode(&to_base64(&[VERSION, size, 0])), Err(DecodeError::InvalidGeometry));
+        }
+    }
+
+    #[test]
+    fn test_unrepresentable_puzzles_are_refused() {
+        let jigsaw = GenerateOptions { jigsaw: true, ..GenerateOptions::default() };
+        let puzzle = generator::generate_with_budget("Easy", &jigsaw, &mut ChaCha8Rng::seed_from_u64(1), Budget::default(), |_| true)
+            .unwrap().puzzle;
+        assert_eq!(encode(&puzzle), Err(EncodeError::VariantRules));
+
+        let plain = Puzzle { geometry: Geometry::latin(4), grid: vec![-1; 16], ..Puzzle::default() };
+        let caged = Puzzle { cages: vec![Cage { cells: vec![0, 1], sum: 3 }], ..plain.clone() };
+        assert_eq!(encode(&caged), Err(EncodeError::VariantRules));
+        let diagonal = Puzzle { constraints: vec![Inequality { a: 0, b: 1 }, Inequality { a: 0, b: 5 }], ..plain };
+        assert_eq!(encode(&diagonal), Err(EncodeError::NonAdjacentInequality { index: 1 }));
+    }
+}
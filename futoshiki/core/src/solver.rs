@@ -1,23 +1,135 @@
-use crate::sudoku::Grid;
-use crate::futoshiki::Inequality;
+//! Backtracking search with constraint propagation.
+//!
+//! Domains are candidate bitmasks (bit v-1 = value v). Propagation keeps a
+//! worklist of cells whose domains changed and re-checks only what touches
+//! them: the peers of a newly fixed cell, the inequalities, cages, dots and
+//! thermometers on the cell, and hidden singles in its row, column and box.
 
-const ALL_ALLOWED: u16 = 0x1FF;
+use serde::Serialize;
+use crate::sudoku::Grid;
+use crate::futoshiki::{Cage, Constraint, DotKind, Inequality, Thermo};
+use crate::geometry::Geometry;
 
+/// Candidate mask of every cell.
 #[derive(Clone)]
-struct State {
-    domains: [u16; 81],
+pub(crate) struct State {
+    pub(crate) domains: Vec<u16>,
+}
+
+/// Data shared by every search node.
+struct Board<'a> {
+    all_values: u16,
+    /// Cells of every row, column and box.
+    houses: Vec<Vec<usize>>,
+    /// Indices into `houses` for each cell.
+    houses_of: Vec<Vec<usize>>,
+    peers: Vec<Vec<usize>>,
+    constraints: &'a [Constraint],
+    /// Indices into `constraints` for each cell.
+    constraints_of: Vec<Vec<usize>>,
+}
+
+/// How much work a search did.
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct SolverStats {
+    /// Search nodes visited, the root included.
+    pub nodes: u64,
+    /// Branches whose value led straight to a contradiction.
+    pub backtracks: u64,
+    /// Deepest branching level reached; 0 if propagation alone decided.
+    pub max_depth: usize,
+    /// Cells and houses taken off the worklist and re-checked.
+    pub propagation_passes: u64,
+    /// Empty cells fixed by propagation before the first branch.
+    pub fixed_before_branch: usize,
+}
+
+/// Bookkeeping for one search: what it found and how much work it did.
+struct Search {
+    solutions: Vec<Grid>,
+    limit: usize,
+    stats: SolverStats,
+    max_nodes: u64,
+    /// Set when the search stopped at `max_nodes` rather than finishing.
+    aborted: bool,
+}
+
+/// Cells and houses waiting to be re-checked, each queued at most once.
+struct Worklist {
+    cells: Vec<usize>,
+    cell_queued: Vec<bool>,
+    houses: Vec<usize>,
+    house_queued: Vec<bool>,
+    /// Entries processed so far, for `SolverStats::propagation_passes`.
+    passes: u64,
+}
+
+impl<'a> Board<'a> {
+    fn new(geometry: &Geometry, constraints: &'a [Constraint]) -> Self {
+        let cells = geometry.cells();
+        let houses: Vec<Vec<usize>> = geometry.houses().iter().map(|h| h.cells(geometry)).collect();
+        let mut houses_of = vec![Vec::new(); cells];
+        for (h, house) in houses.iter().enumerate() {
+            for &idx in house {
+                houses_of[idx].push(h);
+            }
+        }
+        let peers = (0..cells)
+            .map(|idx| {
+                let mut peers: Vec<usize> = houses_of[idx].iter()
+                    .flat_map(|&h| houses[h].iter().copied())
+                    .filter(|&j| j != idx)
+                    .collect();
+                peers.sort_unstable();
+                peers.dedup();
+                peers
+            })
+            .collect();
+        let mut constraints_of = vec![Vec::new(); cells];
+        for (k, constraint) in constraints.iter().enumerate() {
+            for idx in constraint.cells() {
+                constraints_of[idx].push(k);
+            }
+        }
+        Board { all_values: geometry.all_values(), houses, houses_of, peers, constraints, constraints_of }
+    }
+}
+
+impl Worklist {
+    fn new(board: &Board) -> Self {
+        Worklist {
+            cells: Vec::new(),
+            cell_queued: vec![false; board.peers.len()],
+            houses: Vec::new(),
+            house_queued: vec![false; board.houses.len()],
+            passes: 0,
+        }
+    }
+
+    fn push(&mut self, idx: usize, board: &Board) {
+        if !self.cell_queued[idx] {
+            self.cell_queued[idx] = true;
+            self.cells.push(idx);
+        }
+        for &h in &board.houses_of[idx] {
+            if !self.house_queued[h] {
+                self.house_queued[h] = true;
+                self.houses.push(h);
+            }
+        }
+    }
 }
 
 impl State {
-    fn new() -> Self {
-        State { domains: [ALL_ALLOWED; 81] }
+    pub(crate) fn new(geometry: &Geometry) -> Self {
+        State { domains: vec![geometry.all_values(); geometry.cells()] }
     }
 
-    fn apply_initial(&mut self, grid: &Grid) -> bool {
-        for i in 0..81 {
-            if grid[i] > 0 {
-                let mask = 1 << (grid[i] - 1);
-                if !self.restrict(i, mask) {
+    fn apply_initial(&mut self, grid: &Grid, board: &Board, work: &mut Worklist) -> bool {
+        for (i, &val) in grid.iter().enumerate() {
+            if val > 0 {
+                let mask = 1 << (val - 1);
+                if !self.restrict(i, mask, board, work) {
                     return false;
                 }
             }
@@ -25,7 +137,9 @@ impl State {
         true
     }
 
-    fn restrict(&mut self, idx: usize, mask: u16) -> bool {
+    /// Narrows the domain of `idx` to `mask`, queueing the cell if it
+    /// shrank. False if nothing is left.
+    fn restrict(&mut self, idx: usize, mask: u16, board: &Board, work: &mut Worklist) -> bool {
         let current = self.domains[idx];
         let new_domain = current & mask;
         if new_domain == current {
@@ -35,176 +149,306 @@ impl State {
             return false;
         }
         self.domains[idx] = new_domain;
+        work.push(idx, board);
         true
     }
 }
 
-pub fn count_solutions(initial_grid: &Grid, constraints: &[Inequality], limit: usize) -> usize {
-    let mut state = State::new();
-    if !state.apply_initial(initial_grid) {
-        return 0;
+pub fn count_solutions(geometry: &Geometry, initial_grid: &Grid, constraints: &[Constraint], limit: usize) -> usize {
+    find_solutions(geometry, initial_grid, constraints, limit).len()
+}
+
+/// The first solution found, if the puzzle has any.
+pub fn solve(geometry: &Geometry, initial_grid: &Grid, constraints: &[Constraint]) -> Option<Grid> {
+    find_solutions(geometry, initial_grid, constraints, 1).pop()
+}
+
+/// Collects up to `limit` solutions, in search order.
+pub fn find_solutions(geometry: &Geometry, initial_grid: &Grid, constraints: &[Constraint], limit: usize) -> Vec<Grid> {
+    run(geometry, initial_grid, constraints, limit, u64::MAX).solutions
+}
+
+/// Like `solve`, but gives up after `max_nodes` search nodes, returning
+/// `None` as if there were no solution.
+pub fn solve_within(geometry: &Geometry, initial_grid: &Grid, constraints: &[Constraint], max_nodes: u64) -> Option<Grid> {
+    run(geometry, initial_grid, constraints, 1, max_nodes).solutions.pop()
+}
+
+/// `count_solutions` that also reports the work the search did.
+pub fn count_solutions_with_stats(geometry: &Geometry, initial_grid: &Grid, constraints: &[Constraint], limit: usize) -> (usize, SolverStats) {
+    let search = run(geometry, initial_grid, constraints, limit, u64::MAX);
+    (search.solutions.len(), search.stats)
+}
+
+/// Like `count_solutions_with_stats`, but gives up after `max_nodes` search
+/// nodes so a caller can bound the time it spends. `None` if the allowance
+/// ran out first.
+pub fn count_solutions_within(
+    geometry: &Geometry,
+    initial_grid: &Grid,
+    constraints: &[Constraint],
+    limit: usize,
+    max_nodes: u64,
+) -> Option<(usize, SolverStats)> {
+    let search = run(geometry, initial_grid, constraints, limit, max_nodes);
+    (!search.aborted).then_some((search.solutions.len(), search.stats))
+}
+
+fn run(geometry: &Geometry, initial_grid: &Grid, constraints: &[Constraint], limit: usize, max_nodes: u64) -> Search {
+    let board = Board::new(geometry, constraints);
+    let mut search = Search { solutions: Vec::new(), limit, stats: SolverStats::default(), max_nodes, aborted: false };
+    let mut state = State::new(geometry);
+    let mut work = Worklist::new(&board);
+    if !state.apply_initial(initial_grid, &board, &mut work) {
+        return search;
     }
-    
-    // Initial propagation of constraints could ideally happen here, 
-    // but we do it inside search for simplicity or pre-process.
-    // For pure solving, we rely on forward checking during search.
-    
-    // Pre-propagate inequalities to prune obviously impossible values
-    // (e.g. if A < B, A cannot be 9, B cannot be 1)
-    // Repeat until stable? For now, just once or iterative inside solver.
-    // Let's do a strong initial propagation loop.
-    if !propagate_all(&mut state, constraints) {
-        return 0;
+
+    // Every inequality and house needs one look even if its cells are
+    // untouched by the givens.
+    for idx in 0..geometry.cells() {
+        work.push(idx, &board);
     }
+    let consistent = propagate(&mut state, &board, &mut work);
+    search.stats.propagation_passes = work.passes;
+    if !consistent {
+        return search;
+    }
+    let givens = initial_grid.iter().filter(|&&v| v > 0).count();
+    let fixed = state.domains.iter().filter(|d| d.count_ones() == 1).count();
+    search.stats.fixed_before_branch = fixed - givens;
 
-    let mut count = 0;
-    solve_recursive(&mut state, constraints, &mut count, limit);
-    count
+    solve_recursive(&mut state, &board, &mut search, 0);
+    search
 }
 
-fn solve_recursive(state: &mut State, constraints: &[Inequality], count: &mut usize, limit: usize) {
-    if *count >= limit {
-        return;
+/// Cells whose values differ between two solutions.
+pub fn differing_cells(a: &Grid, b: &Grid) -> Vec<usize> {
+    (0..a.len()).filter(|&i| a[i] != b[i]).collect()
+}
+
+/// Where a puzzle is ambiguous: the cells that differ between the first
+/// two solutions found. `None` if the puzzle has at most one solution.
+pub fn ambiguous_cells(geometry: &Geometry, initial_grid: &Grid, constraints: &[Constraint]) -> Option<Vec<usize>> {
+    match find_solutions(geometry, initial_grid, constraints, 2).as_slice() {
+        [first, second] => Some(differing_cells(first, second)),
+        _ => None,
     }
+}
 
-    // MRV Heuristic
-    let mut min_len = 10;
-    let mut best_idx = None;
+fn solve_recursive(state: &mut State, board: &Board, search: &mut Search, depth: usize) {
+    if search.solutions.len() >= search.limit || search.aborted {
+        return;
+    }
+    if search.stats.nodes >= search.max_nodes {
+        search.aborted = true;
+        return;
+    }
+    search.stats.nodes += 1;
+    search.stats.max_depth = search.stats.max_depth.max(depth);
 
-    for i in 0..81 {
-        let d = state.domains[i];
+    // Branch on the unfixed cell with the fewest candidates.
+    let mut best: Option<(usize, u32)> = None;
+    for (i, &d) in state.domains.iter().enumerate() {
         let ones = d.count_ones();
-        if ones == 0 { return; } // Should't happen if restrict checks 0
-        if ones == 1 { continue; } // Already assigned
-        if ones < min_len {
-            min_len = ones;
-            best_idx = Some(i);
-            if ones == 2 { break; } // Optimization
+        if ones > 1 && best.is_none_or(|(_, fewest)| ones < fewest) {
+            best = Some((i, ones));
+            if ones == 2 {
+                break;
+            }
         }
     }
 
-    if let Some(idx) = best_idx {
-        let domain = state.domains[idx];
-        for val in 1..=9 {
-            let mask = 1 << (val - 1);
-            if (domain & mask) != 0 {
-                let mut next_state = state.clone();
-                // Assign val to idx
-                if next_state.restrict(idx, mask) {
-                    // Propagate
-                    if propagate_from(&mut next_state, idx, constraints) {
-                        solve_recursive(&mut next_state, constraints, count, limit);
+    let Some((idx, _)) = best else {
+        // Every cell is fixed, and each one was propagated to its peers and
+        // inequalities, so the grid is a solution.
+        search.solutions.push(state.domains.iter().map(|&d| get_min(d)).collect());
+        return;
+    };
+
+    let mut remaining = state.domains[idx];
+    while remaining != 0 {
+        let mask = remaining & remaining.wrapping_neg();
+        remaining &= !mask;
+
+        let mut next_state = state.clone();
+        let mut work = Worklist::new(board);
+        let consistent = next_state.restrict(idx, mask, board, &mut work) && propagate(&mut next_state, board, &mut work);
+        search.stats.propagation_passes += work.passes;
+        if consistent {
+            solve_recursive(&mut next_state, board, search, depth + 1);
+        } else {
+            search.stats.backtracks += 1;
+        }
+        if search.solutions.len() >= search.limit || search.aborted {
+            return;
+        }
+    }
+}
+
+/// Drains the worklist: fixed cells leave their peers, inequalities on a
+/// changed cell are re-checked, and once no cell is pending, changed houses
+/// are scanned for values with one place left. False on a contradiction.
+fn propagate(state: &mut State, board: &Board, work: &mut Worklist) -> bool {
+    loop {
+        while let Some(idx) = work.cells.pop() {
+            work.cell_queued[idx] = false;
+            work.passes += 1;
+            let domain = state.domains[idx];
+            if domain.count_ones() == 1 {
+                for &peer in &board.peers[idx] {
+                    if !state.restrict(peer, !domain, board, work) {
+                        return false;
                     }
                 }
             }
+            for &k in &board.constraints_of[idx] {
+                if !revise(state, &board.constraints[k], board, work) {
+                    return false;
+                }
+            }
+        }
+
+        let Some(h) = work.houses.pop() else {
+            return true;
+        };
+        work.house_queued[h] = false;
+        work.passes += 1;
+        if !hidden_singles(state, &board.houses[h], board, work) {
+            return false;
         }
-    } else {
-        // All variables assigned. Check constraints one last time? 
-        // Forward checking should have guaranteed validity.
-        *count += 1;
     }
 }
 
-fn propagate_from(state: &mut State, changed_idx: usize, constraints: &[Inequality]) -> bool {
-    // Standard Sudoku propagation (Row/Col/Block)
-    if !propagate_sudoku(state, changed_idx) {
+fn revise(state: &mut State, constraint: &Constraint, board: &Board, work: &mut Worklist) -> bool {
+    match constraint {
+        Constraint::Inequality(ineq) => revise_inequality(state, ineq, board, work),
+        Constraint::Cage(cage) => revise_cage(state, cage, board, work),
+        Constraint::Dot(dot) => revise_pair(state, dot.a, dot.b, |x, y| dot.kind.holds(x, y), board, work),
+        Constraint::NoDot { a, b } => {
+            let neither = |x, y| !DotKind::White.holds(x, y) && !DotKind::Black.holds(x, y);
+            revise_pair(state, *a, *b, neither, board, work)
+        }
+        Constraint::Thermo(thermo) => revise_thermo(state, thermo, board, work),
+    }
+}
+
+/// Bounds consistency for `a < b`: `a` stays below the largest candidate
+/// of `b`, and `b` above the smallest candidate of `a`.
+fn revise_inequality(state: &mut State, ineq: &Inequality, board: &Board, work: &mut Worklist) -> bool {
+    let max_b = get_max(state.domains[ineq.b]);
+    if !state.restrict(ineq.a, filter_lt(board.all_values, max_b), board, work) {
         return false;
     }
-    
-    // Inequality propagation 
-    // (Opt: only check constraints involving changed_idx and their neighbors iteratively)
-    // For safety/simplicity, strict check all might be slow. 
-    // Let's do full propagation loop until stable or optimized.
-    propagate_all(state, constraints)
-}
-
-fn propagate_sudoku(state: &mut State, idx: usize) -> bool {
-    let assigned_val = state.domains[idx];
-    // Must be single value
-    if assigned_val.count_ones() != 1 { return true; } 
-    
-    let row = idx / 9;
-    let col = idx % 9;
-    let _blk = (row / 3) * 3 + (col / 3);
-
-    // Eliminate from Row
-    for k in 0..9 {
-        let target = row * 9 + k;
-        if target != idx {
-            if !state.restrict(target, !assigned_val) { return false; }
-        }
-    }
-    // Eliminate from Col
-    for k in 0..9 {
-        let target = k * 9 + col;
-        if target != idx {
-            if !state.restrict(target, !assigned_val) { return false; }
-        }
-    }
-    // Eliminate from Block
-    let start_row = (row / 3) * 3;
-    let start_col = (col / 3) * 3;
-    for r in 0..3 {
-        for c in 0..3 {
-            let target = (start_row + r) * 9 + (start_col + c);
-            if target != idx {
-                if !state.restrict(target, !assigned_val) { return false; }
+    let min_a = get_min(state.domains[ineq.a]);
+    state.restrict(ineq.b, filter_gt(board.all_values, min_a), board, work)
+}
+
+/// A fixed value leaves the rest of its cage, and every cell keeps only
+/// the values that the smallest and largest candidates of the other cells
+/// can still complete to the sum.
+fn revise_cage(state: &mut State, cage: &Cage, board: &Board, work: &mut Worklist) -> bool {
+    for &idx in &cage.cells {
+        let domain = state.domains[idx];
+        if domain.count_ones() == 1 {
+            for &other in cage.cells.iter().filter(|&&other| other != idx) {
+                if !state.restrict(other, !domain, board, work) {
+                    return false;
+                }
             }
         }
     }
+
+    // Bounds gathered up front only get looser as cells shrink below, and
+    // any cell that shrinks brings the cage back through the worklist.
+    let min_total: u32 = cage.cells.iter().map(|&idx| get_min(state.domains[idx]) as u32).sum();
+    let max_total: u32 = cage.cells.iter().map(|&idx| get_max(state.domains[idx]) as u32).sum();
+    for &idx in &cage.cells {
+        let domain = state.domains[idx];
+        let others_min = min_total - get_min(domain) as u32;
+        let others_max = max_total - get_max(domain) as u32;
+        if cage.sum < others_min + 1 {
+            return false;
+        }
+        let low = cage.sum.saturating_sub(others_max).max(1);
+        let high = (cage.sum - others_min).min(16);
+        if low > high {
+            return false;
+        }
+        let range = ((1u32 << high) - 1) & !((1u32 << (low - 1)) - 1);
+        if !state.restrict(idx, board.all_values & range as u16, board, work) {
+            return false;
+        }
+    }
     true
 }
 
-fn propagate_all(state: &mut State, constraints: &[Inequality]) -> bool {
-    let mut changed = true;
-    while changed {
-        changed = false;
-        // Check Sudoku singles? (Expensive to loop 81 every time)
-        // Assume Sudoku handled by assignment trigger.
-        // Focus on Inequalities.
-        
-        for ineq in constraints {
-            let da = state.domains[ineq.a];
-            let db = state.domains[ineq.b];
-
-            // Condition: a < b
-            // min(a) must be < max(b)
-            // max(b) must be > min(a)
-            
-            let min_a = get_min(da);
-            let max_b = get_max(db);
-            
-            // a < max_b -> a <= max_b - 1
-            // Remove values in A >= max_b
-            let new_da = filter_lt(da, max_b); 
-            if new_da != da {
-                if new_da == 0 { return false; }
-                state.domains[ineq.a] = new_da;
-                changed = true;
-            }
+/// Bounds along a thermometer: each cell stays above the smallest candidate
+/// of the cell before it and below the largest of the cell after it. Seen
+/// from the ends, cell `i` of `len` lies in `i + 1..=n - (len - 1 - i)`.
+fn revise_thermo(state: &mut State, thermo: &Thermo, board: &Board, work: &mut Worklist) -> bool {
+    for pair in thermo.cells.windows(2) {
+        let min_before = get_min(state.domains[pair[0]]);
+        if !state.restrict(pair[1], filter_gt(board.all_values, min_before), board, work) {
+            return false;
+        }
+    }
+    for pair in thermo.cells.windows(2).rev() {
+        let max_after = get_max(state.domains[pair[1]]);
+        if !state.restrict(pair[0], filter_lt(board.all_values, max_after), board, work) {
+            return false;
+        }
+    }
+    true
+}
 
-            // b > min_a -> b >= min_a + 1
-            // Remove values in B <= min_a
-            let new_db = filter_gt(db, min_a);
-            if new_db != db {
-                if new_db == 0 { return false; }
-                state.domains[ineq.b] = new_db;
-                changed = true;
-            }
+/// Arc consistency for a symmetric relation between two neighbours: each
+/// keeps only the values that some other candidate of the other cell
+/// relates to. Neighbours share a row or column, so equal values never count.
+fn revise_pair(state: &mut State, a: usize, b: usize, related: impl Fn(u8, u8) -> bool, board: &Board, work: &mut Worklist) -> bool {
+    let supported = |other: u16| {
+        (1..=16u8)
+            .filter(|&x| (1..=16u8).any(|y| x != y && other & 1 << (y - 1) != 0 && related(x, y)))
+            .fold(0u16, |mask, x| mask | 1 << (x - 1))
+    };
+    state.restrict(a, supported(state.domains[b]), board, work)
+        && state.restrict(b, supported(state.domains[a]), board, work)
+}
+
+/// Fixes every cell that is the only place left in `house` for some
+/// value. False if a value has no place or one cell is the only place
+/// for two values.
+fn hidden_singles(state: &mut State, house: &[usize], board: &Board, work: &mut Worklist) -> bool {
+    let (mut once, mut twice) = (0u16, 0u16);
+    for &idx in house {
+        let domain = state.domains[idx];
+        twice |= once & domain;
+        once |= domain;
+    }
+    if once != board.all_values {
+        return false;
+    }
+    let singles = once & !twice;
+    if singles == 0 {
+        return true;
+    }
+    for &idx in house {
+        let only_here = state.domains[idx] & singles;
+        if only_here.count_ones() > 1 || (only_here != 0 && !state.restrict(idx, only_here, board, work)) {
+            return false;
         }
     }
     true
 }
 
-fn get_min(mask: u16) -> u8 {
+pub(crate) fn get_min(mask: u16) -> u8 {
     mask.trailing_zeros() as u8 + 1
 }
 
-fn get_max(mask: u16) -> u8 {
+pub(crate) fn get_max(mask: u16) -> u8 {
     16 - (mask.leading_zeros() as u8) // 16 bits
 }
 
-fn filter_lt(mask: u16, val: u8) -> u16 {
+pub(crate) fn filter_lt(mask: u16, val: u8) -> u16 {
     // Keep values < val
     // (1 << (val-1)) is the bit for val.
     if val <= 1 { return 0; }
@@ -213,7 +457,7 @@ fn filter_lt(mask: u16, val: u8) -> u16 {
     mask & keep_mask
 }
 
-fn filter_gt(mask: u16, val: u8) -> u16 {
+pub(crate) fn filter_gt(mask: u16, val: u8) -> u16 {
     // Keep values > val
     let _limit_bit = 1 << (val - 1); // e.g. val=1 -> bit 0 (1). limit-1=0. mask & ~0 = mask.
     // wait, val=1, keep 2..9. mask > 1.
@@ -221,3 +465,190 @@ fn filter_gt(mask: u16, val: u8) -> u16 {
     let remove_mask = (1 << val) - 1;
     mask & !remove_mask
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::futoshiki::{self, Dot};
+    use crate::sudoku;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn full_grid(geometry: &Geometry) -> Grid {
+        sudoku::generate_complete_grid(geometry, &mut ChaCha8Rng::seed_from_u64(9)).unwrap()
+    }
+
+    #[test]
+    fn test_cells_fixed_by_inequalities_leave_their_peers() {
+        // 8 < cell 2 forces a 9 next to the given 9 in the same row. Only
+        // inequality propagation fixes cell 2, so the duplicate must still
+        // be caught.
+        let mut grid = vec![0; 81];
+        grid[0] = 9;
+        grid[1] = 8;
+        assert_eq!(count_solutions(&Geometry::classic(), &grid, &[Inequality { a: 1, b: 2 }.into()], 1), 0);
+    }
+
+    #[test]
+    fn test_solve_returns_the_solution() {
+        for geometry in [Geometry::classic(), Geometry::latin(6)] {
+            let solution = full_grid(&geometry);
+            let constraints: Vec<Constraint> = futoshiki::generate_from_grid(&geometry, &solution)
+                .into_iter().map(Constraint::from).collect();
+            let mut grid = solution.clone();
+            for idx in (0..geometry.cells()).step_by(3) {
+                grid[idx] = 0;
+            }
+            assert_eq!(solve(&geometry, &grid, &constraints), Some(solution.clone()));
+            assert_eq!(find_solutions(&geometry, &grid, &constraints, 5), vec![solution]);
+            assert_eq!(ambiguous_cells(&geometry, &grid, &constraints), None);
+        }
+    }
+
+    #[test]
+    fn test_ambiguous_cells_locate_the_difference() {
+        // Two rows of one band that swap a pair of values between the same
+        // two columns form a rectangle that can be flipped either way.
+        let geometry = Geometry::classic();
+        let solution = full_grid(&geometry);
+        let corners = (0..9)
+            .flat_map(|r1| (r1 + 1..r1 / 3 * 3 + 3).map(move |r2| (r1, r2)))
+            .flat_map(|(r1, r2)| (0..9).flat_map(move |c1| (c1 + 1..9).map(move |c2| (r1, r2, c1, c2))))
+            .map(|(r1, r2, c1, c2)| [r1 * 9 + c1, r1 * 9 + c2, r2 * 9 + c1, r2 * 9 + c2])
+            .find(|&[a, b, c, d]| solution[a] == solution[d] && solution[b] == solution[c])
+            .unwrap();
+
+        let mut grid = solution.clone();
+        for &i in &corners {
+            grid[i] = 0;
+        }
+        let mut cells = ambiguous_cells(&geometry, &grid, &[]).unwrap();
+        cells.sort();
+        assert_eq!(cells, corners.to_vec());
+        assert!(differing_cells(&solution, &solution).is_empty());
+    }
+
+    #[test]
+    fn test_node_allowance_stops_the_search() {
+        let geometry = Geometry::latin(6);
+        let empty = vec![0u8; geometry.cells()];
+        assert_eq!(count_solutions_within(&geometry, &empty, &[], 2, 3), None);
+
+        let (count, stats) = count_solutions_within(&geometry, &empty, &[], 2, 1000).unwrap();
+        assert_eq!(count, 2);
+        assert!(stats.nodes > 3 && stats.nodes <= 1000);
+    }
+
+    #[test]
+    fn test_hidden_single_is_propagated() {
+        // The 1s in columns 0 and 1 leave only column 2 for the 1 of the
+        // top row, although that cell still has three candidates.
+        let geometry = Geometry::latin(3);
+        let board = Board::new(&geometry, &[]);
+        let mut state = State::new(&geometry);
+        let mut work = Worklist::new(&board);
+        assert!(state.apply_initial(&vec![0, 0, 0, 1, 0, 0, 0, 1, 0], &board, &mut work));
+        assert!(propagate(&mut state, &board, &mut work));
+        assert_eq!(state.domains[2], 0b001);
+        assert_eq!(state.domains[0], 0b110);
+    }
+
+    #[test]
+    fn test_inequality_chain_needs_no_branching() {
+        // 1 < 2 < 3 < 4 along the top row fixes it by bounds alone, and
+        // the Latin 4x4 rest stays open.
+        let geometry = Geometry::latin(4);
+        let constraints: Vec<Constraint> = (0..3).map(|c| Inequality { a: c, b: c + 1 }.into()).collect();
+        let board = Board::new(&geometry, &constraints);
+        let mut state = State::new(&geometry);
+        let mut work = Worklist::new(&board);
+        (0..geometry.cells()).for_each(|idx| work.push(idx, &board));
+        assert!(propagate(&mut state, &board, &mut work));
+        assert_eq!(&state.domains[..4], &[0b0001, 0b0010, 0b0100, 0b1000]);
+        assert_eq!(state.domains[4], 0b1110);
+    }
+
+    #[test]
+    fn test_cage_sum_bounds_its_cells() {
+        // A two-cell cage summing to 3 can only hold 1 and 2, which leaves
+        // the 3 of the top row to the third cell.
+        let geometry = Geometry::latin(3);
+        let constraints = [Constraint::from(Cage { cells: vec![0, 1], sum: 3 })];
+        let board = Board::new(&geometry, &constraints);
+        let mut state = State::new(&geometry);
+        let mut work = Worklist::new(&board);
+        (0..geometry.cells()).for_each(|idx| work.push(idx, &board));
+        assert!(propagate(&mut state, &board, &mut work));
+        assert_eq!(&state.domains[..3], &[0b011, 0b011, 0b100]);
+
+        // A cage whose sum needs a repeated value has no solution.
+        let constraints = [Constraint::from(Cage { cells: vec![0, 1], sum: 6 })];
+        assert_eq!(count_solutions(&geometry, &vec![0; 9], &constraints, 2), 0);
+    }
+
+    #[test]
+    fn test_dots_restrict_their_pair() {
+        // A black dot between the first two cells of a Latin 4x4 leaves
+        // {1, 2} or {2, 4}; fixing cell 1 to 4 forces cell 0 to 2.
+        let geometry = Geometry::latin(4);
+        let constraints = [Constraint::from(Dot { a: 0, b: 1, kind: DotKind::Black })];
+        let board = Board::new(&geometry, &constraints);
+        let mut state = State::new(&geometry);
+        let mut work = Worklist::new(&board);
+        (0..geometry.cells()).for_each(|idx| work.push(idx, &board));
+        assert!(propagate(&mut state, &board, &mut work));
+        assert_eq!(&state.domains[..2], &[0b1011, 0b1011]);
+        assert!(state.apply_initial(&[vec![0, 4], vec![0; 14]].concat(), &board, &mut work));
+        assert!(propagate(&mut state, &board, &mut work));
+        assert_eq!(state.domains[0], 0b0010);
+
+        // Without any relation allowed, 2 has no partner in 1..=4.
+        let constraints = [Constraint::NoDot { a: 0, b: 1 }];
+        let board = Board::new(&geometry, &constraints);
+        let mut state = State::new(&geometry);
+        let mut work = Worklist::new(&board);
+        (0..geometry.cells()).for_each(|idx| work.push(idx, &board));
+        assert!(propagate(&mut state, &board, &mut work));
+        assert_eq!(&state.domains[..2], &[0b1101, 0b1101]);
+    }
+
+    #[test]
+    fn test_thermo_bounds_follow_path_length() {
+        // A 4-cell thermometer on a Latin 6x6 pins its bulb to 1..=3 and
+        // its tip to 4..=6 before anything is placed.
+        let geometry = Geometry::latin(6);
+        let constraints = [Constraint::from(Thermo { cells: vec![0, 7, 8, 15] })];
+        let board = Board::new(&geometry, &constraints);
+        let mut state = State::new(&geometry);
+        let mut work = Worklist::new(&board);
+        (0..geometry.cells()).for_each(|idx| work.push(idx, &board));
+        assert!(propagate(&mut state, &board, &mut work));
+        assert_eq!(state.domains[0], 0b000111);
+        assert_eq!(state.domains[7], 0b001110);
+        assert_eq!(state.domains[15], 0b111000);
+
+        // A thermometer longer than the value range cannot be filled.
+        let constraints = [Constraint::from(Thermo { cells: vec![0, 7, 14, 21, 28, 35, 34] })];
+        assert_eq!(count_solutions(&geometry, &vec![0; 36], &constraints, 2), 0);
+    }
+
+    #[test]
+    fn test_stats_describe_the_search() {
+        let geometry = Geometry::classic();
+        let solution = full_grid(&geometry);
+
+        // A full grid minus one cell is decided by propagation alone.
+        let mut grid = solution.clone();
+        grid[40] = 0;
+        let (count, stats) = count_solutions_with_stats(&geometry, &grid, &[], 2);
+        assert_eq!(count, 1);
+        assert_eq!(stats, SolverStats { nodes: 1, backtracks: 0, max_depth: 0, propagation_passes: stats.propagation_passes, fixed_before_branch: 1 });
+        assert!(stats.propagation_passes > 0);
+
+        // An empty board has to branch, and the first branches never fail.
+        let (count, stats) = count_solutions_with_stats(&geometry, &vec![0; 81], &[], 2);
+        assert_eq!(count, 2);
+        assert_eq!(stats.fixed_before_branch, 0);
+        assert!(stats.max_depth > 0 && stats.nodes > stats.max_depth as u64);
+    }
+}
@@ -1,50 +1,50 @@
 use rand::prelude::*;
+use crate::geometry::Geometry;
+use crate::solver;
 
-pub type Grid = [u8; 81];
+/// Cell values in row-major order (0 = empty).
+pub type Grid = Vec<u8>;
 
-pub fn generate_complete_grid() -> Grid {
-    let mut grid = [0; 81];
-    let mut rng = rand::thread_rng();
-    
-    // We can use a more optimized solver, but for 9x9 generation from scratch,
-    // simple backtracking with randomization is fast enough.
-    assert!(fill_grid(&mut grid, 0, &mut rng));
-    grid
-}
+/// Search nodes allowed when checking that a board can be filled.
+const FILL_NODES: u64 = 10_000;
 
-fn fill_grid(grid: &mut Grid, idx: usize, rng: &mut ThreadRng) -> bool {
-    if idx >= 81 {
-        return true;
+/// Every shuffle is drawn from `rng`, so a seeded generator always
+/// yields the same grid. `None` if no grid fits, which only extra regions
+/// can cause; check `is_fillable` first on boards that have them.
+pub fn generate_complete_grid(geometry: &Geometry, rng: &mut impl Rng) -> Option<Grid> {
+    // We can use a more optimized solver, but for plain boards up to 9x9
+    // generated from scratch, simple backtracking with randomization is
+    // fast enough. Irregular boxes and extra regions can trap it in dead
+    // ends for seconds or minutes, so those boards are filled by the solver.
+    if geometry.jigsaw.is_some() || !geometry.regions.is_empty() {
+        return fill_with_solver(geometry, rng);
     }
+    let mut grid = vec![0; geometry.cells()];
+    let peers: Vec<Vec<usize>> = (0..geometry.cells()).map(|i| geometry.peers(i)).collect();
+    fill_grid(&mut grid, 0, geometry.size, &peers, rng).then_some(grid)
+}
 
-    let row = idx / 9;
-    let col = idx % 9;
-    let _blk = (row / 3) * 3 + (col / 3);
-
-    // Find valid numbers
-    let mut candidates = [true; 10]; // 1..9
-    
-    // Check Row & Col (optimization: check only previous cells)
-    for k in 0..9 {
-        let r_val = grid[row * 9 + k];
-        if r_val != 0 { candidates[r_val as usize] = false; }
-        
-        let c_val = grid[k * 9 + col];
-        if c_val != 0 { candidates[c_val as usize] = false; }
+/// Whether a short solver search finds a grid for `geometry`. Plain boxed
+/// and Latin boards always have one; extra regions or a jigsaw layout can
+/// rule every grid out.
+pub fn is_fillable(geometry: &Geometry) -> bool {
+    solver::solve_within(geometry, &vec![0; geometry.cells()], &[], FILL_NODES).is_some()
+}
+
+fn fill_grid(grid: &mut Grid, idx: usize, size: usize, peers: &[Vec<usize>], rng: &mut impl Rng) -> bool {
+    if idx >= grid.len() {
+        return true;
     }
 
-    // Check Block
-    let start_row = (row / 3) * 3;
-    let start_col = (col / 3) * 3;
-    for r in 0..3 {
-        for c in 0..3 {
-            let val = grid[(start_row + r) * 9 + (start_col + c)];
-            if val != 0 { candidates[val as usize] = false; }
-        }
+    // Find valid numbers: anything not already used by a row, column or box peer
+    let mut candidates = vec![true; size + 1];
+    for &p in &peers[idx] {
+        let val = grid[p];
+        if val != 0 { candidates[val as usize] = false; }
     }
 
     // Collect valid numbers
-    let mut valid_nums: Vec<u8> = (1..=9).filter(|&n| candidates[n as usize]).collect();
+    let mut valid_nums: Vec<u8> = (1..=size as u8).filter(|&n| candidates[n as usize]).collect();
     if valid_nums.is_empty() {
         return false;
     }
@@ -54,7 +54,7 @@ fn fill_grid(grid: &mut Grid, idx: usize, rng: &mut ThreadRng) -> bool {
 
     for num in valid_nums {
         grid[idx] = num;
-        if fill_grid(grid, idx + 1, rng) {
+        if fill_grid(grid, idx + 1, size, peers, rng) {
             return true;
         }
         grid[idx] = 0;
@@ -62,3 +62,137 @@ fn fill_grid(grid: &mut Grid, idx: usize, rng: &mut ThreadRng) -> bool {
 
     false
 }
+
+/// Solver fills tried from random seed values before falling back to an
+/// empty board.
+const SEEDED_FILLS: usize = 10;
+
+/// A solver grid grown from `size` random seed values, one per random
+/// cell and each clear of the seeds among its peers, so the solver does
+/// not hand back the same grid for a fixed board. Seeds that leave no
+/// grid are drawn again; after `SEEDED_FILLS` tries the empty board is
+/// solved and its digits relabelled at random.
+fn fill_with_solver(geometry: &Geometry, rng: &mut impl Rng) -> Option<Grid> {
+    let cells = geometry.cells();
+    for _ in 0..SEEDED_FILLS {
+        let mut seeds = vec![0; cells];
+        for idx in rand::seq::index::sample(rng, cells, geometry.size) {
+            let taken: Vec<u8> = geometry.peers(idx).iter().map(|&peer| seeds[peer]).collect();
+            let free: Vec<u8> = (1..=geometry.size as u8).filter(|value| !taken.contains(value)).collect();
+            if let Some(&value) = free.choose(rng) {
+                seeds[idx] = value;
+            }
+        }
+        if let Some(grid) = solver::solve_within(geometry, &seeds, &[], FILL_NODES) {
+            return Some(grid);
+        }
+    }
+    let grid = solver::solve_within(geometry, &vec![0; cells], &[], FILL_NODES)?;
+    let mut labels: Vec<u8> = (1..=geometry.size as u8).collect();
+    labels.shuffle(rng);
+    Some(grid.iter().map(|&value| labels[value as usize - 1]).collect())
+}
+
+/// Trades tried per cell while shuffling a jigsaw layout.
+const JIGSAW_TRADES_PER_CELL: usize = 20;
+
+/// Random jigsaw layout for `base`, a board without boxes whose extra
+/// regions are kept. Starting from the regular boxes (or the rows, for
+/// sizes without a box shape), two neighbouring boxes repeatedly trade a
+/// cell each: a border cell of one box joins the other, and a cell of the
+/// other box that touches the first moves the opposite way. A trade stands
+/// only if both boxes stay connected. The layout is not checked for a
+/// fill; see `is_fillable`.
+pub fn random_jigsaw_layout(base: &Geometry, rng: &mut impl Rng) -> Geometry {
+    let size = base.size;
+    let start = Geometry::boxed(size).unwrap_or_else(|| Geometry::latin(size));
+    let cells = start.cells();
+    let neighbours: Vec<Vec<usize>> = (0..cells)
+        .map(|i| {
+            let (r, c) = (start.row(i), start.col(i));
+            [
+                (r > 0).then(|| i - size),
+                (r + 1 < size).then(|| i + size),
+                (c > 0).then(|| i - 1),
+                (c + 1 < size).then(|| i + 1),
+            ]
+            .into_iter()
+            .flatten()
+            .collect()
+        })
+        .collect();
+    let mut map: Vec<usize> = (0..cells).map(|i| start.box_of(i).unwrap_or_else(|| start.row(i))).collect();
+    for _ in 0..cells * JIGSAW_TRADES_PER_CELL {
+        // `x` leaves box `a` for the neighbouring box `b`; `y` of box `b`
+        // takes its place in `a`.
+        let x = rng.gen_range(0..cells);
+        let a = map[x];
+        let others: Vec<usize> = neighbours[x].iter().map(|&j| map[j]).filter(|&region| region != a).collect();
+        let Some(&b) = others.choose(rng) else {
+            continue;
+        };
+        let touching: Vec<usize> = (0..cells)
+            .filter(|&y| map[y] == b && neighbours[y].iter().any(|&j| j != x && map[j] == a))
+            .collect();
+        let Some(&y) = touching.choose(rng) else {
+            continue;
+        };
+        map[x] = b;
+        map[y] = a;
+        let connected = |region: usize| {
+            let members: Vec<usize> = (0..cells).filter(|&i| map[i] == region).collect();
+            start.is_connected(&members)
+        };
+        if !connected(a) || !connected(b) {
+            map[x] = a;
+            map[y] = b;
+        }
+    }
+
+    Geometry { jigsaw: Some(map), ..base.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_jigsaw_layouts_are_irregular() {
+        for size in [4, 5, 6, 7, 9] {
+            let regular = Geometry::boxed(size).unwrap_or_else(|| Geometry::latin(size));
+            let regular_map: Vec<usize> = (0..regular.cells()).map(|i| regular.box_of(i).unwrap_or_else(|| regular.row(i))).collect();
+            for seed in 0..5 {
+                let geometry = random_jigsaw_layout(&Geometry::latin(size), &mut ChaCha8Rng::seed_from_u64(seed));
+                let map = geometry.jigsaw.clone().unwrap();
+                assert_ne!(map, regular_map, "size {} seed {}: layout is regular", size, seed);
+                assert_eq!(Geometry::jigsaw(map).map(|jigsaw| jigsaw.size), Some(size));
+            }
+        }
+    }
+
+    #[test]
+    fn test_region_grids_vary() {
+        let size = 9;
+        let diagonals = vec![(0..size).map(|i| i * size + i).collect(), (0..size).map(|i| i * size + size - 1 - i).collect()];
+        let geometry = Geometry::latin(size).with_regions(diagonals).unwrap();
+        let mut shapes = Vec::new();
+        for seed in 0..5 {
+            let grid = generate_complete_grid(&geometry, &mut ChaCha8Rng::seed_from_u64(seed)).unwrap();
+            for idx in 0..grid.len() {
+                assert!(geometry.peers(idx).iter().all(|&peer| grid[peer] != grid[idx]), "seed {}: cell {} repeats", seed, idx);
+            }
+            // Relabel by first appearance so grids that differ only in
+            // their digits compare equal.
+            let mut order: Vec<u8> = Vec::new();
+            for &value in &grid {
+                if !order.contains(&value) {
+                    order.push(value);
+                }
+            }
+            shapes.push(grid.iter().map(|value| order.iter().position(|v| v == value).unwrap()).collect::<Vec<_>>());
+        }
+        shapes.dedup();
+        assert!(shapes.len() > 1);
+    }
+}
@@ -0,0 +1,347 @@
+//! Standalone SVG rendering for printing.
+//!
+//! Inequalities are drawn as chevrons on the edge between the two cells,
+//! with the point towards the smaller one. Box borders are thick. Extra
+//! regions are shaded, thermometers are grey tubes with a bulb, killer
+//! cages are dashed outlines with their sum in the corner, and Kropki dots
+//! are white or black circles on the edge.
+
+use crate::futoshiki::{Cage, DotKind, Puzzle};
+use crate::sudoku::Grid;
+
+const CELL: usize = 48;
+const MARGIN: usize = 12;
+/// Space below each puzzle on a page for its number.
+const CAPTION: usize = 28;
+/// Gap between a cage outline and the cell edge.
+const CAGE_INSET: usize = 4;
+
+/// Renders one puzzle. If `solution` is given, its values fill the empty
+/// cells in a lighter colour.
+pub fn render_svg(puzzle: &Puzzle, solution: Option<&Grid>) -> String {
+    let side = puzzle.geometry.size * CELL + 2 * MARGIN;
+    let mut out = header(side, side);
+    out.push_str(&format!("<g transform=\"translate({MARGIN},{MARGIN})\">\n"));
+    draw_puzzle(&mut out, puzzle, solution);
+    out.push_str("</g>\n</svg>\n");
+    out
+}
+
+/// Lays several puzzles out on one page for a booklet, `columns` per row,
+/// each numbered underneath from 1.
+pub fn render_page(puzzles: &[(&Puzzle, Option<&Grid>)], columns: usize) -> String {
+    let columns = columns.max(1);
+    let rows = puzzles.len().div_ceil(columns);
+    let largest = puzzles.iter().map(|(p, _)| p.geometry.size).max().unwrap_or(0);
+    let slot_w = largest * CELL + 2 * MARGIN;
+    let slot_h = slot_w + CAPTION;
+
+    let mut out = header(columns * slot_w, rows * slot_h);
+    for (k, (puzzle, solution)) in puzzles.iter().enumerate() {
+        let width = puzzle.geometry.size * CELL;
+        // Centre smaller boards in their slot.
+        let x = (k % columns) * slot_w + (slot_w - width) / 2;
+        let y = (k / columns) * slot_h + MARGIN;
+        out.push_str(&format!("<g transform=\"translate({x},{y})\">\n"));
+        draw_puzzle(&mut out, puzzle, *solution);
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-family=\"sans-serif\" font-size=\"16\">{}</text>\n",
+            width / 2, width + CAPTION - 6, k + 1,
+        ));
+        out.push_str("</g>\n");
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn header(width: usize, height: usize) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n\
+         <rect width=\"{width}\" height=\"{height}\" fill=\"#fff\"/>\n"
+    )
+}
+
+/// Draws the grid with its top-left corner at the origin.
+fn draw_puzzle(out: &mut String, puzzle: &Puzzle, solution: Option<&Grid>) {
+    let geometry = &puzzle.geometry;
+    let n = geometry.size;
+    let full = n * CELL;
+    let centre = |idx: usize| (geometry.col(idx) * CELL + CELL / 2, geometry.row(idx) * CELL + CELL / 2);
+
+    // Shading and thermometers go under the grid lines and digits.
+    for region in &geometry.regions {
+        for &idx in region {
+            out.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{CELL}\" height=\"{CELL}\" fill=\"#000\" fill-opacity=\"0.1\"/>\n",
+                geometry.col(idx) * CELL, geometry.row(idx) * CELL,
+            ));
+        }
+    }
+    for thermo in &puzzle.thermos {
+        let Some(&bulb) = thermo.cells.first() else {
+            continue;
+        };
+        let points: Vec<String> = thermo.cells.iter().map(|&idx| {
+            let (x, y) = centre(idx);
+            format!("{x},{y}")
+        }).collect();
+        let (x, y) = centre(bulb);
+        out.push_str(&format!(
+            "<circle cx=\"{x}\" cy=\"{y}\" r=\"{}\" fill=\"#ccc\"/>\n\
+             <path d=\"M{}\" fill=\"none\" stroke=\"#ccc\" stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>\n",
+            CELL * 3 / 8, points.join(" L"), CELL / 4,
+        ));
+    }
+
+    // Inner lines, thick on regular box boundaries, then the outer border.
+    for k in 1..n {
+        let p = k * CELL;
+        let thick_row = geometry.boxes.is_some_and(|b| k % b.rows == 0);
+        let thick_col = geometry.boxes.is_some_and(|b| k % b.cols == 0);
+        out.push_str(&line(0, p, full, p, if thick_row { 3 } else { 1 }));
+        out.push_str(&line(p, 0, p, full, if thick_col { 3 } else { 1 }));
+    }
+    // Jigsaw boxes have no straight boundaries: thicken each edge between
+    // cells of different boxes.
+    if geometry.jigsaw.is_some() {
+        for idx in 0..geometry.cells() {
+            let (r, c) = (geometry.row(idx), geometry.col(idx));
+            if c + 1 < n && geometry.box_of(idx) != geometry.box_of(idx + 1) {
+                out.push_str(&line((c + 1) * CELL, r * CELL, (c + 1) * CELL, (r + 1) * CELL, 3));
+            }
+            if r + 1 < n && geometry.box_of(idx) != geometry.box_of(idx + n) {
+                out.push_str(&line(c * CELL, (r + 1) * CELL, (c + 1) * CELL, (r + 1) * CELL, 3));
+            }
+        }
+    }
+    out.push_str(&format!("<rect width=\"{full}\" height=\"{full}\" fill=\"none\" stroke=\"#000\" stroke-width=\"3\"/>\n"));
+
+    for cage in &puzzle.cages {
+        draw_cage(out, puzzle, cage);
+    }
+
+    for (idx, &given) in puzzle.grid.iter().enumerate().take(geometry.cells()) {
+        let (value, colour) = match (given, solution) {
+            (v, _) if v > 0 => (v as u8, "#000"),
+            (_, Some(solution)) => (solution[idx], "#3a6ea5"),
+            _ => continue,
+        };
+        let x = geometry.col(idx) * CELL + CELL / 2;
+        let y = geometry.row(idx) * CELL + CELL / 2;
+        out.push_str(&format!(
+            "<text x=\"{x}\" y=\"{y}\" text-anchor=\"middle\" dominant-baseline=\"central\" font-family=\"sans-serif\" font-size=\"26\" fill=\"{colour}\">{value}</text>\n"
+        ));
+    }
+
+    for ineq in &puzzle.constraints {
+        let (ra, ca) = (geometry.row(ineq.a), geometry.col(ineq.a));
+        let (rb, cb) = (geometry.row(ineq.b), geometry.col(ineq.b));
+        // (dx, dy) points from the smaller cell `a` towards `b`.
+        let (dx, dy) = (cb as isize - ca as isize, rb as isize - ra as isize);
+        if dx.abs() + dy.abs() != 1 {
+            continue;
+        }
+        // Midpoint of the shared edge.
+        let mx = (ca + cb + 1) * CELL / 2;
+        let my = (ra + rb + 1) * CELL / 2;
+        out.push_str(&chevron(mx as isize, my as isize, dx, dy));
+    }
+
+    for dot in &puzzle.dots {
+        let ((xa, ya), (xb, yb)) = (centre(dot.a), centre(dot.b));
+        let fill = match dot.kind {
+            DotKind::White => "#fff",
+            DotKind::Black => "#000",
+        };
+        out.push_str(&format!(
+            "<circle cx=\"{}\" cy=\"{}\" r=\"5\" fill=\"{fill}\" stroke=\"#000\" stroke-width=\"1.5\"/>\n",
+            (xa + xb) / 2, (ya + yb) / 2,
+        ));
+    }
+}
+
+/// Dashed outline just inside the cage's outer edges, with the sum in the
+/// top-left corner of its first cell. A side runs on to the cell edge where
+/// the cage continues, so the outline closes across cells.
+fn draw_cage(out: &mut String, puzzle: &Puzzle, cage: &Cage) {
+    let geometry = &puzzle.geometry;
+    let n = geometry.size;
+    let inside = |r: usize, c: usize| cage.cells.contains(&(r * n + c));
+    for &idx in &cage.cells {
+        let (r, c) = (geometry.row(idx), geometry.col(idx));
+        let up = r > 0 && inside(r - 1, c);
+        let down = r + 1 < n && inside(r + 1, c);
+        let left = c > 0 && inside(r, c - 1);
+        let right = c + 1 < n && inside(r, c + 1);
+        let x0 = c * CELL + if left { 0 } else { CAGE_INSET };
+        let x1 = (c + 1) * CELL - if right { 0 } else { CAGE_INSET };
+        let y0 = r * CELL + if up { 0 } else { CAGE_INSET };
+        let y1 = (r + 1) * CELL - if down { 0 } else { CAGE_INSET };
+        if !up {
+            out.push_str(&dashed(x0, y0, x1, y0));
+        }
+        if !down {
+            out.push_str(&dashed(x0, y1, x1, y1));
+        }
+        if !left {
+            out.push_str(&dashed(x0, y0, x0, y1));
+        }
+        if !right {
+            out.push_str(&dashed(x1, y0, x1, y1));
+        }
+    }
+    if let Some(&first) = cage.cells.iter().min() {
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"11\">{}</text>\n",
+            geometry.col(first) * CELL + CAGE_INSET + 2, geometry.row(first) * CELL + CAGE_INSET + 10, cage.sum,
+        ));
+    }
+}
+
+fn dashed(x1: usize, y1: usize, x2: usize, y2: usize) -> String {
+    format!("<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"#000\" stroke-width=\"1\" stroke-dasharray=\"3 3\"/>\n")
+}
+
+fn line(x1: usize, y1: usize, x2: usize, y2: usize, width: usize) -> String {
+    format!("<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"#000\" stroke-width=\"{width}\"/>\n")
+}
+
+/// A `<` shape centred on (x, y) whose point faces away from (dx, dy),
+/// on a white patch so it stays readable over the grid line.
+fn chevron(x: isize, y: isize, dx: isize, dy: isize) -> String {
+    const HALF: isize = 5;
+    const SPREAD: isize = 7;
+    // Tip towards the smaller cell, arms towards the larger one.
+    let tip = (x - dx * HALF, y - dy * HALF);
+    let arm1 = (x + dx * HALF + dy * SPREAD, y + dy * HALF + dx * SPREAD);
+    let arm2 = (x + dx * HALF - dy * SPREAD, y + dy * HALF - dx * SPREAD);
+    let (w, h) = if dx != 0 { (2 * HALF + 4, 2 * SPREAD + 4) } else { (2 * SPREAD + 4, 2 * HALF + 4) };
+    format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{w}\" height=\"{h}\" fill=\"#fff\"/>\n\
+         <polyline points=\"{},{} {},{} {},{}\" fill=\"none\" stroke=\"#000\" stroke-width=\"2.5\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>\n",
+        x - w / 2, y - h / 2, arm1.0, arm1.1, tip.0, tip.1, arm2.0, arm2.1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::futoshiki::{Dot, Inequality, Thermo};
+    use crate::geometry::Geometry;
+    use crate::generator;
+    use crate::solver;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_render_puzzle_and_solution() {
+        let puzzle = generator::generate_seeded("Normal", 5).unwrap();
+        let clues = puzzle.grid.iter().filter(|&&v| v > 0).count();
+
+        let svg = render_svg(&puzzle, None);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<text").count(), clues);
+        assert_eq!(svg.matches("<polyline").count(), puzzle.constraints.len());
+        // Two thick inner lines each way on a 3x3-box board.
+        assert_eq!(svg.matches("stroke-width=\"3\"/>").count(), 5);
+
+        let solution = solver::solve(&puzzle.geometry, &puzzle.givens(), &puzzle.rules()).unwrap();
+        let svg = render_svg(&puzzle, Some(&solution));
+        assert_eq!(svg.matches("<text").count(), 81);
+    }
+
+    #[test]
+    fn test_chevron_points_at_smaller_cell() {
+        // Cell 1 < cell 0: the tip sits right of the edge at x = 48.
+        // Cell 0 < cell 2: the tip sits above the edge at y = 48.
+        let puzzle = Puzzle {
+            geometry: Geometry::latin(2),
+            grid: vec![-1; 4],
+            constraints: vec![Inequality { a: 1, b: 0 }, Inequality { a: 0, b: 2 }],
+            ..Puzzle::default()
+        };
+        let svg = render_svg(&puzzle, None);
+        assert!(svg.contains("points=\"43,17 53,24 43,31\""));
+        assert!(svg.contains("points=\"31,53 24,43 17,53\""));
+    }
+
+    #[test]
+    fn test_jigsaw_box_borders() {
+        let map = vec![
+            0, 0, 0, 1,
+            2, 0, 1, 1,
+            2, 3, 3, 1,
+            2, 2, 3, 3,
+        ];
+        let puzzle = Puzzle { geometry: Geometry::jigsaw(map).unwrap(), grid: vec![-1; 16], ..Puzzle::default() };
+        let svg = render_svg(&puzzle, None);
+        // Twelve edges between different boxes, plus the outer border.
+        assert_eq!(svg.matches("stroke-width=\"3\"/>").count(), 13);
+        assert!(svg.contains("x1=\"144\" y1=\"0\" x2=\"144\" y2=\"48\""));
+    }
+
+    #[test]
+    fn test_page_layout() {
+        let a = generator::generate_with_rng("LatinEasy", Some(4), &mut ChaCha8Rng::seed_from_u64(1)).unwrap();
+        let b = generator::generate_seeded("Easy", 1).unwrap();
+        let svg = render_page(&[(&a, None), (&b, None), (&a, None)], 2);
+        assert_eq!(svg.matches("<g ").count(), 3);
+        let slot = 9 * CELL + 2 * MARGIN;
+        assert!(svg.contains(&format!("width=\"{}\" height=\"{}\"", 2 * slot, 2 * (slot + CAPTION))));
+        assert!(svg.contains(">3</text>"));
+    }
+
+    #[test]
+    fn test_cage_outlines() {
+        let puzzle = Puzzle {
+            geometry: Geometry::latin(3),
+            grid: vec![-1; 9],
+            cages: vec![Cage { cells: vec![0, 1, 4], sum: 6 }],
+            ..Puzzle::default()
+        };
+        let svg = render_svg(&puzzle, None);
+        // Cells 0 and 1 share a side, as do 1 and 4: 12 sides, 4 inside.
+        assert_eq!(svg.matches("stroke-dasharray").count(), 8);
+        assert!(svg.contains(">6</text>"));
+    }
+
+    #[test]
+    fn test_kropki_dots() {
+        let puzzle = Puzzle {
+            geometry: Geometry::latin(3),
+            grid: vec![-1; 9],
+            dots: vec![Dot { a: 2, b: 5, kind: DotKind::White }, Dot { a: 6, b: 7, kind: DotKind::Black }],
+            ..Puzzle::default()
+        };
+        let svg = render_svg(&puzzle, None);
+        assert!(svg.contains("<circle cx=\"120\" cy=\"48\" r=\"5\" fill=\"#fff\""));
+        assert!(svg.contains("<circle cx=\"48\" cy=\"120\" r=\"5\" fill=\"#000\""));
+    }
+
+    #[test]
+    fn test_thermometers() {
+        let puzzle = Puzzle {
+            geometry: Geometry::latin(3),
+            grid: vec![-1; 9],
+            thermos: vec![Thermo { cells: vec![3, 6, 7] }, Thermo { cells: Vec::new() }],
+            ..Puzzle::default()
+        };
+        let svg = render_svg(&puzzle, None);
+        // The empty thermometer is skipped.
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert!(svg.contains("<circle cx=\"24\" cy=\"72\""));
+        assert!(svg.contains("d=\"M24,72 L24,120 L72,120\""));
+    }
+
+    #[test]
+    fn test_extra_regions_are_shaded() {
+        let puzzle = Puzzle {
+            geometry: Geometry::latin(3).with_regions(vec![vec![0, 4, 8]]).unwrap(),
+            grid: vec![-1; 9],
+            ..Puzzle::default()
+        };
+        let svg = render_svg(&puzzle, None);
+        assert_eq!(svg.matches("fill-opacity").count(), 3);
+        assert!(svg.contains("<rect x=\"48\" y=\"48\""));
+    }
+}
@@ -0,0 +1,312 @@
+//! Plain-text puzzle formats used by other puzzle collections.
+//!
+//! * Sudoku strings: one character per cell in row-major order, `.` or `0`
+//!   for blanks, e.g. the common 81-character form for 9x9. Clues above 9
+//!   are written as `A`, `B`, `C`.
+//! * ASCII grids: one line per row of cells, with `<` or `>` between
+//!   horizontal neighbours, and a line between rows with `^` or `v` under
+//!   each column. The narrow end points at the smaller cell:
+//!
+//!   ```text
+//!   1<. .
+//!       v
+//!   . . 2
+//!   ^
+//!   . .>.
+//!   ```
+
+use std::fmt;
+use crate::futoshiki::{Inequality, Puzzle};
+use crate::geometry::{Geometry, MAX_SIZE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnexpectedChar(char),
+    /// A clue larger than the board size.
+    ValueOutOfRange(char),
+    /// A Sudoku string whose length is not the cell count of a boxed board.
+    InvalidLength(usize),
+    /// A row of cells with a different number of cells than the first one.
+    RowLength { expected: usize, found: usize },
+    /// The number of cell rows does not match the number of columns.
+    RowCount { expected: usize, found: usize },
+    /// A grid with more cells per row than `geometry::MAX_SIZE`.
+    TooLarge(usize),
+    Empty,
+}
+
+/// Where and why parsing failed. Lines and columns start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match self.kind {
+            ParseErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            ParseErrorKind::ValueOutOfRange(c) => write!(f, "clue '{}' is larger than the board", c),
+            ParseErrorKind::InvalidLength(len) => write!(f, "{} cells do not form a supported board", len),
+            ParseErrorKind::RowLength { expected, found } => write!(f, "expected {} cells in the row, found {}", expected, found),
+            ParseErrorKind::RowCount { expected, found } => write!(f, "expected {} rows, found {}", expected, found),
+            ParseErrorKind::TooLarge(size) => write!(f, "{}x{} is larger than the biggest supported board", size, size),
+            ParseErrorKind::Empty => write!(f, "no puzzle found"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    fn new(line: usize, column: usize, kind: ParseErrorKind) -> Self {
+        ParseError { line, column, kind }
+    }
+}
+
+/// Parses a Sudoku string. Whitespace (including line breaks) is skipped;
+/// the cell count picks the board: 16, 36, 81 or 144 cells.
+pub fn parse_sudoku_string(text: &str) -> Result<Puzzle, ParseError> {
+    let mut cells = Vec::new();
+    let mut last = (1, 1);
+    for (line_no, line) in text.lines().enumerate() {
+        for (col_no, ch) in line.chars().enumerate() {
+            last = (line_no + 1, col_no + 2);
+            if !ch.is_whitespace() {
+                cells.push((ch, line_no + 1, col_no + 1));
+            }
+        }
+    }
+    if cells.is_empty() {
+        return Err(ParseError::new(1, 1, ParseErrorKind::Empty));
+    }
+
+    let size = (1..=12).find(|n| n * n == cells.len());
+    let geometry = size.and_then(Geometry::boxed)
+        .ok_or(ParseError::new(last.0, last.1, ParseErrorKind::InvalidLength(cells.len())))?;
+    let grid = cells.into_iter()
+        .map(|(ch, line, column)| parse_cell(ch, geometry.size).map_err(|kind| ParseError::new(line, column, kind)))
+        .collect::<Result<Vec<i8>, ParseError>>()?;
+
+    Ok(Puzzle { geometry, grid, ..Puzzle::default() })
+}
+
+/// Writes the clues of `puzzle` as a Sudoku string, `.` for blanks.
+/// Inequalities are not part of the format and are left out.
+pub fn to_sudoku_string(puzzle: &Puzzle) -> String {
+    puzzle.grid.iter().map(|&v| cell_char(v)).collect()
+}
+
+/// Parses an ASCII grid. The format has no box borders, so the result is a
+/// Latin board; set `geometry` afterwards for a boxed one. Trailing spaces
+/// may be omitted and blank lines at either end are ignored.
+pub fn parse_ascii_grid(text: &str) -> Result<Puzzle, ParseError> {
+    let lines: Vec<&str> = text.lines().collect();
+    let first = lines.iter().position(|l| !l.trim().is_empty())
+        .ok_or(ParseError::new(1, 1, ParseErrorKind::Empty))?;
+    let last = lines.iter().rposition(|l| !l.trim().is_empty()).unwrap();
+
+    let width = lines[first].trim_end().chars().count();
+    let size = width.div_ceil(2);
+    if size > MAX_SIZE {
+        return Err(ParseError::new(first + 1, 2 * MAX_SIZE + 1, ParseErrorKind::TooLarge(size)));
+    }
+    let mut grid = Vec::with_capacity(size * size);
+    let mut constraints = Vec::new();
+
+    for (k, line) in lines[first..=last].iter().enumerate() {
+        let line_no = first + k + 1;
+        let row = k / 2;
+        let chars: Vec<char> = line.trim_end().chars().collect();
+
+        if k % 2 == 0 {
+            if row >= size {
+                return Err(ParseError::new(line_no, 1, ParseErrorKind::RowCount { expected: size, found: row + 1 }));
+            }
+            let found = chars.len().div_ceil(2);
+            if chars.len().is_multiple_of(2) || found != size {
+                return Err(ParseError::new(line_no, chars.len() + 1, ParseErrorKind::RowLength { expected: size, found }));
+            }
+            for (column, &ch) in chars.iter().enumerate() {
+                let err = |kind| ParseError::new(line_no, column + 1, kind);
+                if column % 2 == 0 {
+                    grid.push(parse_cell(ch, size).map_err(err)?);
+                    continue;
+                }
+                let (left, right) = (row * size + column / 2, row * size + column / 2 + 1);
+                match ch {
+                    ' ' => {}
+                    '<' => constraints.push(Inequality { a: left, b: right }),
+                    '>' => constraints.push(Inequality { a: right, b: left }),
+                    _ => return Err(err(ParseErrorKind::UnexpectedChar(ch))),
+                }
+            }
+        } else {
+            for (column, &ch) in chars.iter().enumerate() {
+                let err = |kind| ParseError::new(line_no, column + 1, kind);
+                if column % 2 == 1 || column / 2 >= size {
+                    if ch != ' ' {
+                        return Err(err(ParseErrorKind::UnexpectedChar(ch)));
+                    }
+                    continue;
+                }
+                let (upper, lower) = (row * size + column / 2, (row + 1) * size + column / 2);
+                match ch {
+                    ' ' => {}
+                    '^' => constraints.push(Inequality { a: upper, b: lower }),
+                    'v' | 'V' => constraints.push(Inequality { a: lower, b: upper }),
+                    _ => return Err(err(ParseErrorKind::UnexpectedChar(ch))),
+                }
+            }
+        }
+    }
+
+    let rows = grid.len() / size;
+    if rows != size || (last - first) % 2 == 1 {
+        return Err(ParseError::new(last + 1, 1, ParseErrorKind::RowCount { expected: size, found: rows }));
+    }
+    Ok(Puzzle { geometry: Geometry::latin(size), grid, constraints, ..Puzzle::default() })
+}
+
+/// Writes `puzzle` as an ASCII grid. Inequalities between cells that are
+/// not orthogonal neighbours cannot be shown and are left out.
+pub fn to_ascii_grid(puzzle: &Puzzle) -> String {
+    let n = puzzle.geometry.size;
+    let mut out = String::new();
+    let relation = |a: usize, b: usize, less: char, greater: char| {
+        if puzzle.constraints.contains(&Inequality { a, b }) {
+            less
+        } else if puzzle.constraints.contains(&Inequality { a: b, b: a }) {
+            greater
+        } else {
+            ' '
+        }
+    };
+
+    for row in 0..n {
+        let mut line = String::new();
+        for col in 0..n {
+            let idx = row * n + col;
+            line.push(cell_char(puzzle.grid[idx]));
+            if col + 1 < n {
+                line.push(relation(idx, idx + 1, '<', '>'));
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+
+        if row + 1 < n {
+            let mut line = String::new();
+            for col in 0..n {
+                let idx = row * n + col;
+                line.push(relation(idx, idx + n, '^', 'v'));
+                if col + 1 < n {
+                    line.push(' ');
+                }
+            }
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn parse_cell(ch: char, size: usize) -> Result<i8, ParseErrorKind> {
+    let value = match ch {
+        '.' | '0' => return Ok(-1),
+        '1'..='9' => ch as u8 - b'0',
+        'A'..='C' => ch as u8 - b'A' + 10,
+        'a'..='c' => ch as u8 - b'a' + 10,
+        _ => return Err(ParseErrorKind::UnexpectedChar(ch)),
+    };
+    if value as usize > size {
+        return Err(ParseErrorKind::ValueOutOfRange(ch));
+    }
+    Ok(value as i8)
+}
+
+fn cell_char(value: i8) -> char {
+    match value {
+        1..=9 => (b'0' + value as u8) as char,
+        10..=12 => (b'A' + value as u8 - 10) as char,
+        _ => '.',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn sorted(mut constraints: Vec<Inequality>) -> Vec<Inequality> {
+        constraints.sort_by_key(|c| (c.a, c.b));
+        constraints
+    }
+
+    #[test]
+    fn test_sudoku_string() {
+        let text = "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+        let puzzle = parse_sudoku_string(text).unwrap();
+        assert_eq!(puzzle.geometry, Geometry::classic());
+        assert_eq!(&puzzle.grid[..4], &[5, 3, -1, -1]);
+        assert_eq!(to_sudoku_string(&puzzle), text);
+
+        let zeros = text.replace('.', "0");
+        assert_eq!(parse_sudoku_string(&zeros).unwrap(), puzzle);
+
+        let err = parse_sudoku_string(&text.replacen('7', "x", 1)).unwrap_err();
+        assert_eq!(err, ParseError::new(1, 5, ParseErrorKind::UnexpectedChar('x')));
+        assert!(matches!(parse_sudoku_string(&text[..80]).unwrap_err().kind, ParseErrorKind::InvalidLength(80)));
+    }
+
+    #[test]
+    fn test_ascii_grid() {
+        let text = "1<. .\n    v\n. . 2\n^\n. .>.\n";
+        let puzzle = parse_ascii_grid(text).unwrap();
+        assert_eq!(puzzle.geometry, Geometry::latin(3));
+        assert_eq!(puzzle.grid, vec![1, -1, -1, -1, -1, 2, -1, -1, -1]);
+        assert_eq!(sorted(puzzle.constraints.clone()), vec![
+            Inequality { a: 0, b: 1 },
+            Inequality { a: 3, b: 6 },
+            Inequality { a: 5, b: 2 },
+            Inequality { a: 8, b: 7 },
+        ]);
+        assert_eq!(to_ascii_grid(&puzzle), text);
+    }
+
+    #[test]
+    fn test_ascii_round_trip() {
+        let puzzle = generator::generate_with_rng("LatinNormal", Some(6), &mut ChaCha8Rng::seed_from_u64(4)).unwrap();
+        let parsed = parse_ascii_grid(&to_ascii_grid(&puzzle)).unwrap();
+        assert_eq!(parsed.grid, puzzle.grid);
+        assert_eq!(sorted(parsed.constraints), sorted(puzzle.constraints));
+    }
+
+    #[test]
+    fn test_ascii_errors_point_at_the_character() {
+        let err = parse_ascii_grid("1<.\n  x\n. .\n").unwrap_err();
+        assert_eq!(err, ParseError::new(2, 3, ParseErrorKind::UnexpectedChar('x')));
+
+        let err = parse_ascii_grid("1 4\n\n. .\n").unwrap_err();
+        assert_eq!(err, ParseError::new(1, 3, ParseErrorKind::ValueOutOfRange('4')));
+
+        let err = parse_ascii_grid("1 2\n\n. . .\n").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert!(matches!(err.kind, ParseErrorKind::RowLength { expected: 2, found: 3 }));
+
+        let err = parse_ascii_grid("1 2\n").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::RowCount { expected: 2, found: 1 }));
+
+        // The first cell past MAX_SIZE is the one at fault.
+        let blank = |size: usize| vec![vec!["."; size].join(" "); size].join("\n\n");
+        assert_eq!(parse_ascii_grid(&blank(MAX_SIZE)).unwrap().geometry.size, MAX_SIZE);
+        for size in [MAX_SIZE + 1, 17] {
+            let err = parse_ascii_grid(&blank(size)).unwrap_err();
+            assert_eq!(err, ParseError::new(1, 2 * MAX_SIZE + 1, ParseErrorKind::TooLarge(size)));
+        }
+    }
+}
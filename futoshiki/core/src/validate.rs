@@ -0,0 +1,268 @@
+//! Rule checking for a board in progress, shared by every front end.
+
+use serde::Serialize;
+use crate::futoshiki::{Cage, Constraint, DotKind, Puzzle};
+use crate::sudoku::Grid;
+use crate::geometry::{Geometry, House};
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Conflict {
+    /// `value` appears more than once in `house`.
+    Duplicate {
+        house: House,
+        value: u8,
+        cells: Vec<usize>,
+    },
+    /// Both cells are filled but `value[a] < value[b]` does not hold.
+    /// `index` points into `Puzzle::constraints`.
+    Inequality {
+        index: usize,
+        a: usize,
+        b: usize,
+    },
+    /// `value` appears more than once in a cage. `index` points into
+    /// `Puzzle::cages`.
+    CageDuplicate {
+        index: usize,
+        value: u8,
+        cells: Vec<usize>,
+    },
+    /// The filled cells of a cage already add up to more than its sum, or
+    /// every cell is filled and they add up to something else.
+    CageSum {
+        index: usize,
+        sum: u32,
+        found: u32,
+    },
+    /// Both cells are filled but their dot does not hold. `index` points
+    /// into `Puzzle::dots`.
+    Dot {
+        index: usize,
+        a: usize,
+        b: usize,
+    },
+    /// Both cells of a thermometer segment are filled but the value does
+    /// not rise from `a` to `b`. `index` points into `Puzzle::thermos`.
+    Thermo {
+        index: usize,
+        a: usize,
+        b: usize,
+    },
+    /// With `Puzzle::all_dots`, two filled neighbours without a dot whose
+    /// values differ by 1 or are in a 1:2 ratio.
+    MissingDot {
+        a: usize,
+        b: usize,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Validation {
+    pub conflicts: Vec<Conflict>,
+    /// Every cell holds a value.
+    pub complete: bool,
+    /// Complete and free of conflicts.
+    pub correct: bool,
+}
+
+/// Checks the givens of `puzzle` overlaid with `entries` (one per cell,
+/// 0 or -1 = empty). Entries on given cells are ignored.
+pub fn validate_board(puzzle: &Puzzle, entries: &[i8]) -> Validation {
+    let board = puzzle.board(entries);
+
+    let mut conflicts = duplicates(&puzzle.geometry, &board);
+    for (index, ineq) in puzzle.constraints.iter().enumerate() {
+        let (va, vb) = (board[ineq.a], board[ineq.b]);
+        if va != 0 && vb != 0 && va >= vb {
+            conflicts.push(Conflict::Inequality { index, a: ineq.a, b: ineq.b });
+        }
+    }
+    for (index, cage) in puzzle.cages.iter().enumerate() {
+        cage_conflicts(index, cage, &board, &mut conflicts);
+    }
+    for (index, dot) in puzzle.dots.iter().enumerate() {
+        let (va, vb) = (board[dot.a], board[dot.b]);
+        if va != 0 && vb != 0 && !dot.kind.holds(va, vb) {
+            conflicts.push(Conflict::Dot { index, a: dot.a, b: dot.b });
+        }
+    }
+    for (index, thermo) in puzzle.thermos.iter().enumerate() {
+        for pair in thermo.cells.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if board[a] != 0 && board[b] != 0 && board[a] >= board[b] {
+                conflicts.push(Conflict::Thermo { index, a, b });
+            }
+        }
+    }
+    if puzzle.all_dots {
+        for rule in puzzle.rules() {
+            let Constraint::NoDot { a, b } = rule else {
+                continue;
+            };
+            let (va, vb) = (board[a], board[b]);
+            if va != 0 && vb != 0 && (DotKind::White.holds(va, vb) || DotKind::Black.holds(va, vb)) {
+                conflicts.push(Conflict::MissingDot { a, b });
+            }
+        }
+    }
+
+    let complete = board.iter().all(|&v| v != 0);
+    Validation {
+        correct: complete && conflicts.is_empty(),
+        conflicts,
+        complete,
+    }
+}
+
+fn duplicates(geometry: &Geometry, board: &Grid) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    for house in geometry.houses() {
+        for value in 1..=geometry.size as u8 {
+            let cells: Vec<usize> = house.cells(geometry).into_iter()
+                .filter(|&i| board[i] == value)
+                .collect();
+            if cells.len() > 1 {
+                conflicts.push(Conflict::Duplicate { house, value, cells });
+            }
+        }
+    }
+    conflicts
+}
+
+fn cage_conflicts(index: usize, cage: &Cage, board: &Grid, conflicts: &mut Vec<Conflict>) {
+    let mut values: Vec<u8> = cage.cells.iter().map(|&i| board[i]).filter(|&v| v != 0).collect();
+    let found: u32 = values.iter().map(|&v| v as u32).sum();
+    if found > cage.sum || (values.len() == cage.cells.len() && found != cage.sum) {
+        conflicts.push(Conflict::CageSum { index, sum: cage.sum, found });
+    }
+    values.sort_unstable();
+    values.dedup();
+    for value in values {
+        let cells: Vec<usize> = cage.cells.iter().copied().filter(|&i| board[i] == value).collect();
+        if cells.len() > 1 {
+            conflicts.push(Conflict::CageDuplicate { index, value, cells });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::futoshiki::{self, Cage, Dot, Inequality, Thermo};
+    use crate::sudoku;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn solved_puzzle() -> (Puzzle, Grid) {
+        let geometry = Geometry::classic();
+        let solution = sudoku::generate_complete_grid(&geometry, &mut ChaCha8Rng::seed_from_u64(5)).unwrap();
+        let mut grid = vec![-1i8; 81];
+        grid[0] = solution[0] as i8;
+        let constraints = futoshiki::generate_from_grid(&geometry, &solution);
+        (Puzzle { geometry, grid, constraints, ..Puzzle::default() }, solution)
+    }
+
+    #[test]
+    fn test_solution_is_correct() {
+        let (puzzle, solution) = solved_puzzle();
+        let entries: Vec<i8> = solution.iter().map(|&v| v as i8).collect();
+        let result = validate_board(&puzzle, &entries);
+        assert!(result.conflicts.is_empty());
+        assert!(result.complete && result.correct);
+    }
+
+    #[test]
+    fn test_swapped_cells_report_conflicts() {
+        let (puzzle, solution) = solved_puzzle();
+        let mut entries: Vec<i8> = solution.iter().map(|&v| v as i8).collect();
+        entries.swap(10, 11);
+        let result = validate_board(&puzzle, &entries);
+        assert!(result.complete && !result.correct);
+
+        // Swapping row neighbours flips their inequality and duplicates
+        // both values in their columns.
+        let index = puzzle.constraints.iter()
+            .position(|c| (c.a == 10 && c.b == 11) || (c.a == 11 && c.b == 10))
+            .unwrap();
+        let Inequality { a, b } = puzzle.constraints[index];
+        assert!(result.conflicts.contains(&Conflict::Inequality { index, a, b }));
+        assert!(result.conflicts.iter().any(|c| matches!(c, Conflict::Duplicate { house: House::Column(1), .. })));
+    }
+
+    #[test]
+    fn test_partial_board_is_incomplete() {
+        let (puzzle, solution) = solved_puzzle();
+        let mut entries = vec![-1i8; 81];
+        entries[1] = solution[1] as i8;
+        entries[2] = solution[1] as i8;
+        let result = validate_board(&puzzle, &entries);
+        assert!(!result.complete && !result.correct);
+        assert!(result.conflicts.contains(&Conflict::Duplicate {
+            house: House::Row(0),
+            value: solution[1],
+            cells: vec![1, 2],
+        }));
+    }
+
+    #[test]
+    fn test_extra_regions_are_checked() {
+        let geometry = Geometry::latin(4).with_regions(vec![vec![0, 5, 10, 15]]).unwrap();
+        let puzzle = Puzzle { geometry, grid: vec![-1; 16], ..Puzzle::default() };
+        let mut entries = vec![-1i8; 16];
+        entries[0] = 2;
+        entries[15] = 2;
+        let result = validate_board(&puzzle, &entries);
+        assert_eq!(result.conflicts, vec![Conflict::Duplicate { house: House::Region(0), value: 2, cells: vec![0, 15] }]);
+    }
+
+    #[test]
+    fn test_cages_are_checked() {
+        let latin = |cages| Puzzle { geometry: Geometry::latin(3), grid: vec![-1; 9], cages, ..Puzzle::default() };
+
+        // A valid Latin square whose first cage adds up to 4, not 3.
+        let puzzle = latin(vec![Cage { cells: vec![0, 1], sum: 3 }]);
+        let result = validate_board(&puzzle, &[3, 1, 2, 1, 2, 3, 2, 3, 1]);
+        assert!(result.complete && !result.correct);
+        assert_eq!(result.conflicts, vec![Conflict::CageSum { index: 0, sum: 3, found: 4 }]);
+
+        // Partly filled: an unfinished cage only fails once it overshoots.
+        let puzzle = latin(vec![Cage { cells: vec![0, 1, 3], sum: 4 }]);
+        let mut entries = vec![-1i8; 9];
+        entries[0] = 3;
+        assert!(validate_board(&puzzle, &entries).conflicts.is_empty());
+        entries[1] = 2;
+        assert_eq!(validate_board(&puzzle, &entries).conflicts, vec![Conflict::CageSum { index: 0, sum: 4, found: 5 }]);
+
+        // Cells 0 and 4 share no row or column, only the cage.
+        let puzzle = latin(vec![Cage { cells: vec![0, 4], sum: 4 }]);
+        let result = validate_board(&puzzle, &[2, 3, 1, 3, 2, 1, 1, 1, 1]);
+        assert!(result.conflicts.contains(&Conflict::CageDuplicate { index: 0, value: 2, cells: vec![0, 4] }));
+    }
+
+    #[test]
+    fn test_dots_are_checked() {
+        let dots = vec![Dot { a: 0, b: 1, kind: DotKind::White }, Dot { a: 1, b: 2, kind: DotKind::Black }];
+        let puzzle = Puzzle { geometry: Geometry::latin(4), grid: vec![-1; 16], dots, ..Puzzle::default() };
+        let mut entries = vec![-1i8; 16];
+        entries[..3].copy_from_slice(&[1, 2, 4]);
+        assert!(validate_board(&puzzle, &entries).conflicts.is_empty());
+        entries[..3].copy_from_slice(&[2, 3, 4]);
+        assert_eq!(validate_board(&puzzle, &entries).conflicts, vec![Conflict::Dot { index: 1, a: 1, b: 2 }]);
+
+        // Every other neighbour pair must satisfy neither dot.
+        let puzzle = Puzzle { all_dots: true, ..puzzle };
+        entries[..4].copy_from_slice(&[1, 2, 4, 3]);
+        assert_eq!(validate_board(&puzzle, &entries).conflicts, vec![Conflict::MissingDot { a: 2, b: 3 }]);
+    }
+
+    #[test]
+    fn test_thermometers_are_checked() {
+        let thermos = vec![Thermo { cells: vec![0, 1, 2] }];
+        let puzzle = Puzzle { geometry: Geometry::latin(3), grid: vec![-1; 9], thermos, ..Puzzle::default() };
+        assert!(validate_board(&puzzle, &[1, 2, 3, 2, 3, 1, 3, 1, 2]).correct);
+        let result = validate_board(&puzzle, &[1, 3, 2, 2, 1, 3, 3, 2, 1]);
+        assert!(result.complete && !result.correct);
+        assert_eq!(result.conflicts, vec![Conflict::Thermo { index: 0, a: 1, b: 2 }]);
+    }
+}